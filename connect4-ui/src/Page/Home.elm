@@ -4,6 +4,7 @@ import Html exposing (Html, div, text)
 import Html.Attributes exposing (class)
 import Html.Events exposing (onClick)
 import Html.Keyed
+import Json.Decode as Decode
 import Json.Encode as Encode
 import List.Extra as List
 import Ports
@@ -43,6 +44,22 @@ difficultyToStr d =
             "hard"
 
 
+{-| Easier levels play like a person would, straying from the best move and
+blundering now and then, instead of just looking fewer moves ahead
+-}
+difficultyToPersonality : Difficulty -> Maybe String
+difficultyToPersonality d =
+    case d of
+        Easy ->
+            Just "beginner"
+
+        Medium ->
+            Just "casual"
+
+        Hard ->
+            Nothing
+
+
 type alias Board =
     List (List Player)
 
@@ -67,14 +84,27 @@ emptyBoard =
 encodeBoard : Difficulty -> Player -> Board -> Encode.Value
 encodeBoard difficulty player board =
     Encode.object
-        [ ( "difficulty", Encode.string (difficultyToStr difficulty) )
-        , ( "player", Encode.string (playerToStr player) )
-        , ( "board"
-          , Encode.list
-                (Encode.list (playerToStr >> Encode.string))
-                board
-          )
-        ]
+        ([ ( "difficulty", Encode.string (difficultyToStr difficulty) )
+         , ( "player", Encode.string (playerToStr player) )
+         , ( "board"
+           , Encode.list
+                 (Encode.list (playerToStr >> Encode.string))
+                 board
+           )
+         ]
+            ++ (case difficultyToPersonality difficulty of
+                    Just personality ->
+                        [ ( "personality", Encode.string personality ) ]
+
+                    Nothing ->
+                        []
+               )
+        )
+
+
+isBoardFull : Board -> Bool
+isBoardFull =
+    List.all (List.length >> (==) maxTokens)
 
 
 dropToken : Int -> Player -> Board -> Result String Board
@@ -107,6 +137,7 @@ type alias Model =
     , board : Board
     , err : Maybe String
     , winner : Maybe Player
+    , isDraw : Bool
     }
 
 
@@ -117,6 +148,7 @@ init =
     , board = emptyBoard
     , err = Nothing
     , winner = Nothing
+    , isDraw = False
     }
 
 
@@ -125,6 +157,7 @@ type Msg
     | Restart
     | AiMoveFinished ( Int, Bool )
     | HasPlayerWon Bool
+    | GameOutcome Decode.Value
 
 
 subs : Model -> Sub Msg
@@ -132,6 +165,7 @@ subs model =
     Sub.batch
         [ Ports.aiMoveFinished AiMoveFinished
         , Ports.hasPlayerWon HasPlayerWon
+        , Ports.gameOutcome GameOutcome
         ]
 
 
@@ -139,7 +173,7 @@ update : Msg -> Model -> ( Model, Cmd Msg )
 update msg model =
     case msg of
         DropToken col ->
-            if Nothing /= model.winner then
+            if Nothing /= model.winner || model.isDraw then
                 ( model, Cmd.none )
 
             else
@@ -163,7 +197,19 @@ update msg model =
             ( { model | winner = Just Player }, Cmd.none )
 
         HasPlayerWon False ->
-            ( model, Ports.runAiMove (encodeBoard model.difficulty Ai model.board) )
+            if isBoardFull model.board then
+                ( model, Cmd.none )
+
+            else
+                ( model, Ports.runAiMove (encodeBoard model.difficulty Ai model.board) )
+
+        GameOutcome outcome ->
+            case Decode.decodeValue (Decode.field "status" Decode.string) outcome of
+                Ok "draw" ->
+                    ( { model | isDraw = True }, Cmd.none )
+
+                _ ->
+                    ( model, Cmd.none )
 
         Restart ->
             ( init, Cmd.none )
@@ -242,6 +288,16 @@ view model =
 
             Nothing ->
                 text ""
+
+        -- Draw
+        , if model.isDraw then
+            div [ class "winner -draw" ]
+                [ div [ class "msg" ] [ text "It's a draw!" ]
+                , Html.button [ class "try-again", onClick Restart ] [ text "Try again" ]
+                ]
+
+          else
+            text ""
         ]
 
 
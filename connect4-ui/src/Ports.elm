@@ -1,10 +1,12 @@
 port module Ports exposing
     ( aiMoveFinished
     , checkPlayerWin
+    , gameOutcome
     , hasPlayerWon
     , runAiMove
     )
 
+import Json.Decode as Decode
 import Json.Encode as Encode
 
 
@@ -18,3 +20,9 @@ port checkPlayerWin : Encode.Value -> Cmd msg
 
 
 port hasPlayerWon : (Bool -> msg) -> Sub msg
+
+
+{-| Outcome of the game after every move, with its `status` being
+"in\_progress", "won" or "draw"
+-}
+port gameOutcome : (Decode.Value -> msg) -> Sub msg
@@ -19,6 +19,7 @@ declare global {
     interface Window {
         connect4_ai_move: (c: number, w: boolean) => void;
         connect4_player_win: (w: boolean) => void;
+        connect4_game_outcome: (o: string) => void;
         connect4_err: (e: string) => void;
     }
 }
@@ -42,6 +43,12 @@ document.addEventListener("DOMContentLoaded", async () => {
         }
     }
 
+    window.connect4_game_outcome = function (outcome: string) {
+        if (elmApp.ports.gameOutcome) {
+            elmApp.ports.gameOutcome.send(JSON.parse(outcome));
+        }
+    }
+
     window.connect4_err = function (err: string) {
         console.error("Connect4 WASM error: ", err);
     }
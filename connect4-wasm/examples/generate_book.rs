@@ -0,0 +1,26 @@
+//! Generates the opening book embedded in the crate, solving every position up
+//! to the given number of plies, however long it takes.
+//!
+//! cargo run --release --example generate_book -- <plies> <output>
+
+use std::env;
+use std::fs;
+use std::io::Write;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let plies: u8 = args.get(1).and_then(|arg| arg.parse().ok()).unwrap_or(1);
+    let output = args
+        .get(2)
+        .cloned()
+        .unwrap_or_else(|| String::from("src/book.bin"));
+
+    let book = connect4::generate_book(plies, &mut |solved, visited| {
+        eprint!("\r{} of {} positions solved", solved, visited);
+        let _ = std::io::stderr().flush();
+    });
+    eprintln!();
+
+    fs::write(&output, &book).expect("Couldn't write the book");
+    eprintln!("Wrote {} bytes to {}", book.len(), output);
+}
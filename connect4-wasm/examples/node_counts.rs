@@ -0,0 +1,27 @@
+//! Prints the nodes the minimax search goes through at every difficulty, with
+//! and without move ordering, over a few positions.
+//!
+//! cargo run --release --example node_counts -- <max depth>
+
+use std::env;
+
+fn main() {
+    let max_depth: u8 = env::args()
+        .nth(1)
+        .and_then(|arg| arg.parse().ok())
+        .unwrap_or(10);
+    let counts = connect4::count_nodes_by_difficulty(&connect4::BENCHMARK_POSITIONS, max_depth)
+        .expect("Benchmark positions are all legal");
+
+    println!(
+        "{:<10} {:>5} {:>14} {:>14} {:>10}",
+        "difficulty", "depth", "unordered", "ordered", "reduction"
+    );
+    counts.iter().for_each(|count| {
+        let reduction = 100.0 * (1.0 - count.ordered as f64 / count.unordered as f64);
+        println!(
+            "{:<10} {:>5} {:>14} {:>14} {:>9.1}%",
+            count.difficulty, count.depth, count.unordered, count.ordered, reduction
+        );
+    });
+}
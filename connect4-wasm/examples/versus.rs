@@ -0,0 +1,21 @@
+//! Plays a match between two difficulties, given the same way as in the JSON
+//! input, and prints the results of the first one.
+//!
+//! cargo run --release --example versus -- '"no_chance"' '{"mcts":{"time_ms":200}}' 10
+
+use std::env;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let first = args.get(1).map_or(r#""no_chance""#, |arg| arg.as_str());
+    let second = args.get(2).map_or(r#""mcts""#, |arg| arg.as_str());
+    let games = args.get(3).and_then(|arg| arg.parse().ok()).unwrap_or(10);
+
+    match connect4::play_match(first, second, games) {
+        Ok(result) => println!(
+            "{} against {}: {} won, {} lost, {} drawn",
+            first, second, result.wins, result.losses, result.draws
+        ),
+        Err(err) => eprintln!("Couldn't play the match: {}", err),
+    }
+}
@@ -0,0 +1,190 @@
+//! Analysis of a position, scoring every column the current player could play,
+//! for showing hints, or how good each move is.
+
+use crate::connect4::Connect4;
+use crate::enums::difficulty::{difficulty_to_depth, Difficulty};
+use crate::evaluation::Weight;
+use crate::minimax::analyze_columns;
+use crate::score::Score;
+use crate::solver::Solver;
+#[cfg(test)]
+use crate::test_support::{drawn_game, game_after};
+use crate::transposition::TranspositionTable;
+use serde::{Deserialize, Serialize};
+
+/// Deepest the analysis looks, as every column is searched in full. Matches
+/// the hardest difficulty.
+const MAX_ANALYSIS_DEPTH: u8 = 10;
+
+/// What playing a column leads to, from the point of view of the player
+/// playing it. Wins and losses count plies (single moves of either player)
+/// from the current position, the move being analysed being the first one.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MoveValue {
+    Win {
+        plies: u8,
+    },
+    Loss {
+        plies: u8,
+    },
+    /// Game ends in a draw, whatever anyone plays.
+    Draw,
+    /// Game isn't decided within the depth searched.
+    Heuristic {
+        weight: Weight,
+    },
+}
+
+/// Score of a column, along with how it reads in the UI, like "win in 5".
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColumnAnalysis {
+    pub col: u8, // Counted from one
+    pub value: MoveValue,
+    pub label: String,
+}
+
+/// Analyses every playable column, looking as deep as the difficulty of the
+/// game allows. On the perfect difficulty the position is solved, however
+/// long it takes, so every value is exact.
+pub fn analyze(game: &Connect4, table: &mut TranspositionTable) -> Vec<ColumnAnalysis> {
+    if game.difficulty == Difficulty::Perfect {
+        return solve(game);
+    }
+    analyze_to_depth(game, analysis_depth(game.difficulty), table)
+}
+
+/// Exact value of every playable column, with both players playing perfectly
+/// after it. Can take a long time early in the game.
+pub fn solve(game: &Connect4) -> Vec<ColumnAnalysis> {
+    to_column_analysis(Solver::init().get_column_scores(game), true)
+}
+
+pub fn analyze_to_depth(
+    game: &Connect4,
+    depth: u8,
+    table: &mut TranspositionTable,
+) -> Vec<ColumnAnalysis> {
+    // When the search reaches the end of the game on every line, the weights
+    // are exact, so anything that isn't a win or a loss is a draw.
+    let reaches_the_end = game.get_board().count_moves_left() <= depth.saturating_add(1);
+
+    to_column_analysis(analyze_columns(game, depth, table), reaches_the_end)
+}
+
+fn to_column_analysis(scores: Vec<(u8, Score)>, reaches_the_end: bool) -> Vec<ColumnAnalysis> {
+    scores
+        .into_iter()
+        .map(|(col, score)| {
+            let value = score_to_value(score, reaches_the_end);
+            ColumnAnalysis {
+                col,
+                value,
+                label: match value {
+                    MoveValue::Draw => String::from("draw"),
+                    _ => score.to_string(),
+                },
+            }
+        })
+        .collect()
+}
+
+fn analysis_depth(difficulty: Difficulty) -> u8 {
+    difficulty_to_depth(difficulty).min(MAX_ANALYSIS_DEPTH)
+}
+
+fn score_to_value(score: Score, reaches_the_end: bool) -> MoveValue {
+    match (score.get_plies(), score.get_heuristic()) {
+        (Option::Some(plies), _) if score.is_win() => MoveValue::Win { plies },
+        (Option::Some(plies), _) => MoveValue::Loss { plies },
+        _ if reaches_the_end => MoveValue::Draw,
+        (_, weight) => MoveValue::Heuristic {
+            weight: weight.unwrap_or(0),
+        },
+    }
+}
+
+// Module tests!!
+
+#[cfg(test)]
+fn value_of(analysis: &[ColumnAnalysis], col: u8) -> MoveValue {
+    analysis.iter().find(|a| a.col == col).unwrap().value
+}
+
+#[test]
+fn finds_immediate_wins_and_losses() {
+    // Player one has three in column 1, the AI has two in column 2, and it's
+    // player one's turn.
+    let game = game_after(Difficulty::Hard, "121217");
+    let analysis = analyze_to_depth(&game, 3, &mut TranspositionTable::init_default());
+
+    assert_eq!(analysis.len(), 7);
+    assert_eq!(value_of(&analysis, 1), MoveValue::Win { plies: 1 });
+
+    // AI to move, it has to block, or player one wins on the next move.
+    let game = game_after(Difficulty::Hard, "12121");
+    let analysis = analyze_to_depth(&game, 3, &mut TranspositionTable::init_default());
+
+    assert!(!matches!(value_of(&analysis, 1), MoveValue::Loss { .. }));
+    (2..=7).for_each(|col| {
+        assert_eq!(value_of(&analysis, col), MoveValue::Loss { plies: 2 });
+    });
+}
+
+#[test]
+fn finds_wins_a_few_moves_ahead() {
+    // Player one can make an open three on the bottom row with column 3 or 6,
+    // which wins on their next move after.
+    let game = game_after(Difficulty::Hard, "4455");
+    let analysis = analyze_to_depth(&game, 4, &mut TranspositionTable::init_default());
+
+    assert_eq!(value_of(&analysis, 3), MoveValue::Win { plies: 3 });
+    assert_eq!(value_of(&analysis, 6), MoveValue::Win { plies: 3 });
+    assert!(matches!(
+        value_of(&analysis, 1),
+        MoveValue::Heuristic { .. }
+    ));
+}
+
+#[test]
+fn finds_draws_at_the_end_of_the_game() {
+    // Same full board as in the draw tests, with the last two cells of column
+    // 7 left empty.
+    let game = drawn_game(Difficulty::Hard, 2);
+
+    let analysis = analyze(&game, &mut TranspositionTable::init_default());
+    assert_eq!(
+        analysis,
+        vec![ColumnAnalysis {
+            col: 7,
+            value: MoveValue::Draw,
+            label: String::from("draw")
+        }]
+    );
+}
+
+#[test]
+fn analysis_serializes_for_the_ui() {
+    let game = game_after(Difficulty::Hard, "4455");
+    let analysis = analyze_to_depth(&game, 4, &mut TranspositionTable::init_default());
+    let json = serde_json::to_string(&analysis[2]).unwrap();
+
+    assert_eq!(
+        json,
+        r#"{"col":3,"value":{"kind":"win","plies":3},"label":"win in 3"}"#
+    );
+    assert!(analysis[0].label.starts_with('+') || analysis[0].label.starts_with('-'));
+}
+
+#[test]
+fn solving_gives_exact_values() {
+    // AI is to move, and loses whatever it plays, at best on the
+    // fourth ply.
+    let game = game_after(Difficulty::Perfect, "2252576253462244111563365343671351441");
+    let analysis = analyze(&game, &mut TranspositionTable::init_default());
+
+    assert!(analysis
+        .iter()
+        .all(|column| matches!(column.value, MoveValue::Loss { .. })));
+    assert!(analysis.iter().any(|column| column.label == "loss in 4"));
+}
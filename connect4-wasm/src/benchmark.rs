@@ -0,0 +1,136 @@
+//! Node counts of the minimax search at every difficulty searching to a fixed
+//! depth, with the columns searched from left to right, and in the order the
+//! move ordering puts them. Printed by the `node_counts` example.
+//!
+//! Also plays matches between difficulties, like minimax against MCTS, for
+//! the `versus` example.
+
+use crate::connect4::Connect4;
+use crate::enums::difficulty::{difficulty_to_depth, Difficulty};
+use crate::enums::game_outcome::GameOutcome;
+use crate::enums::player::Player;
+use crate::error::Connect4Error;
+use crate::minimax::{count_nodes, play_for_ai};
+
+/// Positions the counts are added up over, as the columns played from the
+/// start of the game.
+pub const BENCHMARK_POSITIONS: [&str; 6] = ["", "4", "4453", "3435", "1234567", "44444435"];
+
+const DIFFICULTIES: [Difficulty; 6] = [
+    Difficulty::Test,
+    Difficulty::Easy,
+    Difficulty::Normal,
+    Difficulty::Hard,
+    Difficulty::VeryHard,
+    Difficulty::NoChance,
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeCount {
+    pub difficulty: String,
+    pub depth: u8,
+    pub unordered: u64,
+    pub ordered: u64,
+}
+
+/// Nodes searched at every difficulty, up to the depth, added up over the
+/// positions, given as the columns played in them.
+pub fn count_nodes_by_difficulty(
+    positions: &[&str],
+    max_depth: u8,
+) -> Result<Vec<NodeCount>, Connect4Error> {
+    let games = positions
+        .iter()
+        .map(|moves| Connect4::init_from_moves(Difficulty::Normal, moves))
+        .collect::<Result<Vec<Connect4>, Connect4Error>>()?;
+
+    Ok(DIFFICULTIES
+        .iter()
+        .filter(|difficulty| difficulty_to_depth(**difficulty) <= max_depth)
+        .map(|difficulty| {
+            let depth = difficulty_to_depth(*difficulty);
+            let total = |ordered| {
+                games
+                    .iter()
+                    .map(|game| count_nodes(game, depth, ordered))
+                    .sum()
+            };
+            NodeCount {
+                difficulty: format!("{:?}", difficulty),
+                depth,
+                unordered: total(false),
+                ordered: total(true),
+            }
+        })
+        .collect())
+}
+
+/// Results of a match, for the first of the two difficulties.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct MatchResult {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+/// Plays games between two difficulties, given the same way as in the JSON
+/// input, taking turns at moving first. Every game is seeded with its number,
+/// so the same match always plays out the same way.
+pub fn play_match(first: &str, second: &str, games: u32) -> Result<MatchResult, Connect4Error> {
+    let first = serde_json::from_str::<Difficulty>(first)?;
+    let second = serde_json::from_str::<Difficulty>(second)?;
+
+    let mut result = MatchResult::default();
+    (0..games).for_each(|game_idx| {
+        let first_starts = game_idx % 2 == 0;
+        let mut game = Connect4::init_vs_ai(first);
+        game.seed = Option::Some(game_idx as u64);
+
+        // Player one always moves first.
+        while game.get_outcome() == GameOutcome::InProgress {
+            let first_to_move = (game.get_current_player() == Player::One) == first_starts;
+            game.difficulty = if first_to_move { first } else { second };
+
+            let col = play_for_ai(&game).unwrap();
+            game.drop_token_in_col(col).unwrap();
+            game.switch_players();
+        }
+
+        match game.get_outcome() {
+            GameOutcome::Won(player, _) if (player == Player::One) == first_starts => {
+                result.wins += 1
+            }
+            GameOutcome::Won(_, _) => result.losses += 1,
+            _ => result.draws += 1,
+        }
+    });
+    Ok(result)
+}
+
+// Module tests!!
+
+#[test]
+fn ordering_searches_fewer_nodes() {
+    let counts = count_nodes_by_difficulty(&BENCHMARK_POSITIONS, 5).unwrap();
+    assert_eq!(counts.len(), 4);
+
+    counts.iter().for_each(|count| {
+        assert!(count.ordered <= count.unordered, "{:?}", count);
+    });
+    // Deeper searches prune a lot more.
+    let hard = counts.last().unwrap();
+    assert_eq!(hard.difficulty, "Hard");
+    assert!(hard.ordered * 2 < hard.unordered, "{:?}", hard);
+
+    assert!(count_nodes_by_difficulty(&["44x"], 5).is_err());
+}
+
+#[test]
+fn matches_play_out_the_same_every_time() {
+    let mcts = r#"{"mcts":{"iterations":200}}"#;
+    let result = play_match(r#""easy""#, mcts, 2).unwrap();
+
+    assert_eq!(result.wins + result.losses + result.draws, 2);
+    assert_eq!(play_match(r#""easy""#, mcts, 2).unwrap(), result);
+    assert!(play_match(r#""hrad""#, mcts, 2).is_err());
+}
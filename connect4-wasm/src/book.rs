@@ -0,0 +1,366 @@
+//! Opening book, with the best move and the exact value of every position
+//! early in the game, which take the solver the longest. It's generated ahead of time
+//! by the `generate_book` example, with the crate's own solver, and embedded
+//! in the binary.
+//!
+//! The book starts with a header, the magic bytes `C4BK`, the format version,
+//! and the number of plies (tokens on the board) it goes up to. It's followed
+//! by one little-endian `u64` per position, sorted so they can be looked up
+//! with a binary search. Every entry packs the key of the position in the top
+//! 50 bits, the best column in the next 7, and its value in the lowest 7.
+//! Positions and their mirror images share an entry, under the lower of their
+//! keys.
+
+use crate::connect4::Connect4;
+use crate::enums::difficulty::Difficulty;
+use crate::enums::game_outcome::GameOutcome;
+use crate::models::board::{mirror_col, COL_NUM};
+use crate::score::Score;
+use crate::solver::Solver;
+#[cfg(test)]
+use crate::test_support::game_after;
+use std::collections::HashSet;
+
+const BOOK_BYTES: &[u8] = include_bytes!("book.bin");
+
+const MAGIC: &[u8; 4] = b"C4BK";
+const VERSION: u8 = 2;
+const HEADER_LEN: usize = 8;
+const ENTRY_LEN: usize = 8;
+
+/// Columns tried first when picking between moves with the same value.
+const COLUMN_ORDER: [u8; COL_NUM as usize] = [4, 3, 5, 2, 6, 1, 7];
+
+/// Table size of the solver generating the book, bigger than the default as
+/// it's kept between all the positions.
+const GENERATOR_TABLE_SIZE: usize = 16_777_259;
+
+/// Best move in a position, and its exact score, with wins and losses counted
+/// in plies from the position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BookMove {
+    pub col: u8, // Counted from one
+    pub score: Score,
+}
+
+/// View over the encoded book.
+#[derive(Debug, Clone, Copy)]
+pub struct Book<'a> {
+    plies: u8,
+    entries: &'a [u8],
+}
+
+impl<'a> Book<'a> {
+    /// Reads the header of the book. Returns None if it isn't a book, or one
+    /// in a format this version doesn't know.
+    pub fn init(bytes: &'a [u8]) -> Option<Book<'a>> {
+        if bytes.len() < HEADER_LEN
+            || &bytes[0..4] != MAGIC
+            || bytes[4] != VERSION
+            || !(bytes.len() - HEADER_LEN).is_multiple_of(ENTRY_LEN)
+        {
+            return Option::None;
+        }
+
+        Option::Some(Book {
+            plies: bytes[5],
+            entries: &bytes[HEADER_LEN..],
+        })
+    }
+
+    /// Deepest ply the book covers.
+    pub fn get_plies(&self) -> u8 {
+        self.plies
+    }
+
+    /// Number of positions in the book.
+    pub fn len(&self) -> usize {
+        self.entries.len() / ENTRY_LEN
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Best move for the side to move, if the position is in the book.
+    pub fn get(&self, game: &Connect4) -> Option<BookMove> {
+        let board = game.get_board();
+        let tokens = board.get_played_mask().count_ones();
+        if tokens > self.plies as u32 || game.get_outcome() != GameOutcome::InProgress {
+            return Option::None;
+        }
+
+        let (key, mirrored) = board.get_canonical_position_key();
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let entry = self.entry(mid);
+            match (entry >> 14).cmp(&key) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => {
+                    return Option::Some(orient(decode_move(entry), mirrored))
+                }
+            }
+        }
+        Option::None
+    }
+
+    fn entry(&self, idx: usize) -> u64 {
+        let mut bytes = [0; ENTRY_LEN];
+        bytes.copy_from_slice(&self.entries[idx * ENTRY_LEN..(idx + 1) * ENTRY_LEN]);
+        u64::from_le_bytes(bytes)
+    }
+}
+
+/// Looks the position up in the book embedded in the binary.
+pub fn lookup(game: &Connect4) -> Option<BookMove> {
+    Book::init(BOOK_BYTES).and_then(|book| book.get(game))
+}
+
+/// Solves every position reachable in up to `max_plies` plies, however long
+/// it takes, and encodes the best move of each into a book. Every position up
+/// to that ply is in the book. Reports the number of positions solved, and
+/// visited, after every position.
+pub fn generate_book(max_plies: u8, on_progress: &mut dyn FnMut(usize, usize)) -> Vec<u8> {
+    let root = Connect4::init_vs_ai(Difficulty::Perfect);
+    generate_book_from(&root, max_plies, on_progress)
+}
+
+/// Same as `generate_book`, but only with positions reachable from the root.
+fn generate_book_from(
+    root: &Connect4,
+    max_plies: u8,
+    on_progress: &mut dyn FnMut(usize, usize),
+) -> Vec<u8> {
+    let mut solver = Solver::init_with_size(GENERATOR_TABLE_SIZE);
+
+    let mut seen = HashSet::new();
+    let mut entries = vec![];
+    let mut frontier = vec![root.clone()];
+    let root_plies = root.get_board().get_played_mask().count_ones() as u8;
+
+    (root_plies..=max_plies).for_each(|ply| {
+        let mut next_frontier = vec![];
+
+        frontier.iter().for_each(|game| {
+            let (key, mirrored) = game.get_board().get_canonical_position_key();
+            if !seen.insert(key) || game.get_outcome() != GameOutcome::InProgress {
+                return;
+            }
+
+            let scores = solver.get_column_scores(game);
+            if let Option::Some(best) = best_move(&scores) {
+                entries.push(encode_entry(key, &orient(best, mirrored)));
+            }
+            on_progress(entries.len(), seen.len());
+
+            if ply < max_plies {
+                scores.iter().for_each(|(col, _)| {
+                    let mut next = game.clone();
+                    next.drop_token_in_col(*col).unwrap();
+                    next.switch_players();
+                    next_frontier.push(next);
+                });
+            }
+        });
+        frontier = next_frontier;
+    });
+
+    entries.sort_unstable();
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + entries.len() * ENTRY_LEN);
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&[VERSION, max_plies, 0, 0]);
+    entries
+        .iter()
+        .for_each(|entry| bytes.extend_from_slice(&entry.to_le_bytes()));
+    bytes
+}
+
+/// Best scoring column, preferring columns closer to the centre on ties.
+fn best_move(scores: &[(u8, Score)]) -> Option<BookMove> {
+    // Of equal elements, `max_by_key` keeps the last one, hence the order.
+    COLUMN_ORDER
+        .iter()
+        .rev()
+        .filter_map(|col| scores.iter().find(|(c, _)| c == col))
+        .max_by_key(|(_, score)| *score)
+        .map(|(col, score)| BookMove {
+            col: *col,
+            score: *score,
+        })
+}
+
+/// Moves are kept as they're played in the canonical position, of the
+/// position and its mirror image, so the column is mirrored going from one to
+/// the other.
+fn orient(book_move: BookMove, mirrored: bool) -> BookMove {
+    BookMove {
+        col: if mirrored {
+            mirror_col(book_move.col)
+        } else {
+            book_move.col
+        },
+        ..book_move
+    }
+}
+
+/// Values are kept as the plies to a win, negative for a loss, and zero for a
+/// draw.
+fn encode_entry(key: u64, book_move: &BookMove) -> u64 {
+    let value = match book_move.score.get_plies() {
+        Option::Some(plies) if book_move.score.is_win() => plies as i8,
+        Option::Some(plies) => -(plies as i8),
+        Option::None => 0,
+    };
+    key << 14 | (book_move.col as u64) << 7 | (value as u8 & 0x7f) as u64
+}
+
+fn decode_move(entry: u64) -> BookMove {
+    // Shifting the value up to the top bit and back brings its sign back.
+    let value = ((entry as u8) << 1) as i8 >> 1;
+    BookMove {
+        col: ((entry >> 7) & 0x7f) as u8,
+        score: match value {
+            0 => Score::DRAW,
+            plies if plies > 0 => Score::win_in(plies as u8),
+            plies => Score::loss_in(-plies as u8),
+        },
+    }
+}
+
+// Module tests!!
+
+#[test]
+fn entries_keep_the_move_and_its_value() {
+    let key = game_after(Difficulty::Perfect, "4453")
+        .get_board()
+        .get_position_key();
+    [
+        Score::win_in(1),
+        Score::win_in(37),
+        Score::loss_in(2),
+        Score::loss_in(40),
+        Score::DRAW,
+    ]
+    .iter()
+    .for_each(|score| {
+        let book_move = BookMove {
+            col: 7,
+            score: *score,
+        };
+        let entry = encode_entry(key, &book_move);
+        assert_eq!(entry >> 14, key);
+        assert_eq!(decode_move(entry), book_move);
+    });
+}
+
+#[test]
+fn generated_book_holds_solved_positions() {
+    let root = game_after(Difficulty::Perfect, "2252576253462244111563365343671351441");
+    let bytes = generate_book_from(&root, 39, &mut |_, _| {});
+    let book = Book::init(&bytes).unwrap();
+    assert_eq!(book.get_plies(), 39);
+
+    // Every position which isn't over is in the book.
+    let mut positions = vec![];
+    positions_up_to(&root, 2, &mut positions);
+    let mut solver = Solver::init();
+    positions
+        .iter()
+        .filter(|game| game.get_outcome() == GameOutcome::InProgress)
+        .for_each(|game| {
+            let book_move = book.get(game).unwrap();
+            let scores = solver.get_column_scores(game);
+            let best = scores.iter().map(|(_, score)| *score).max().unwrap();
+
+            assert_eq!(book_move.score, best);
+            assert!(scores.contains(&(book_move.col, best)));
+        });
+}
+
+#[cfg(test)]
+fn positions_up_to(game: &Connect4, plies: u8, positions: &mut Vec<Connect4>) {
+    positions.push(game.clone());
+    if plies == 0 || game.get_outcome() != GameOutcome::InProgress {
+        return;
+    }
+    (1..=COL_NUM as u8).for_each(|col| {
+        let mut next = game.clone();
+        if next.drop_token_in_col(col).is_ok() {
+            next.switch_players();
+            positions_up_to(&next, plies - 1, positions);
+        }
+    });
+}
+
+#[test]
+fn embedded_book_covers_every_position_up_to_its_plies() {
+    let book = Book::init(BOOK_BYTES).unwrap();
+    assert!(book.get_plies() >= 1);
+
+    let mut positions = vec![];
+    positions_up_to(
+        &Connect4::init_vs_ai(Difficulty::Perfect),
+        book.get_plies(),
+        &mut positions,
+    );
+    positions
+        .iter()
+        .for_each(|game| assert!(lookup(game).is_some()));
+}
+
+#[test]
+fn embedded_book_agrees_with_itself() {
+    // Positions of the book take the solver far too long, but every move
+    // leading to another position of the book has to be worth what that
+    // position is, from the other side.
+    let book = Book::init(BOOK_BYTES).unwrap();
+    let mut positions = vec![];
+    positions_up_to(
+        &Connect4::init_vs_ai(Difficulty::Perfect),
+        book.get_plies() - 1,
+        &mut positions,
+    );
+
+    positions.iter().for_each(|game| {
+        let book_move = lookup(game).unwrap();
+        let scores: Vec<(u8, Score)> = (1..=COL_NUM as u8)
+            .map(|col| {
+                let mut next = game.clone();
+                next.drop_token_in_col(col).unwrap();
+                next.switch_players();
+                (col, (-lookup(&next).unwrap().score).to_root(1))
+            })
+            .collect();
+        let best = scores.iter().map(|(_, score)| *score).max().unwrap();
+
+        assert_eq!(book_move.score, best);
+        assert!(scores.contains(&(book_move.col, best)));
+    });
+}
+
+#[test]
+fn mirror_images_share_an_entry() {
+    let game = game_after(Difficulty::Perfect, "2");
+    let mirrored = game_after(Difficulty::Perfect, "6");
+    let book_move = lookup(&game).unwrap();
+
+    assert_eq!(
+        lookup(&mirrored),
+        Option::Some(BookMove {
+            col: mirror_col(book_move.col),
+            score: book_move.score
+        })
+    );
+}
+
+#[test]
+fn bad_books_are_rejected() {
+    assert!(Book::init(b"").is_none());
+    assert!(Book::init(b"C4BK\x09\x04\x00\x00").is_none());
+    assert!(Book::init(b"XXXX\x01\x04\x00\x00").is_none());
+    assert!(Book::init(b"C4BK\x02\x04\x00\x00\x01").is_none());
+    assert!(Book::init(b"C4BK\x02\x04\x00\x00").unwrap().is_empty());
+}
@@ -0,0 +1,45 @@
+//! Callback style API, where results are reported by calling functions the JS
+//! side has installed on `window`. Kept for the UI built around it, newer code
+//! should use the functions returning results instead.
+
+use crate::{ai_move, player_move, MoveResult};
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+extern "C" {
+    pub fn connect4_ai_move(c: u8, w: bool);
+    pub fn connect4_player_win(w: bool);
+    pub fn connect4_game_outcome(outcome: &str);
+    pub fn connect4_err(err: &str);
+}
+
+fn report_error_or_outcome(result: &MoveResult) {
+    if let Option::Some(err) = result.error() {
+        connect4_err(err.as_str());
+    }
+    if let Option::Some(outcome) = result.outcome() {
+        connect4_game_outcome(outcome.as_str());
+    }
+}
+
+#[wasm_bindgen]
+pub fn process_data_for_ai(data: &str) {
+    let result = ai_move(data);
+
+    // Report data...
+    if let Option::Some(c) = result.col() {
+        connect4_ai_move(c, result.won());
+    }
+    report_error_or_outcome(&result);
+}
+
+#[wasm_bindgen]
+pub fn process_data_for_player(data: &str) {
+    let result = player_move(data);
+
+    // Check if player won
+    if result.error().is_none() {
+        connect4_player_win(result.won());
+    }
+    report_error_or_outcome(&result);
+}
@@ -0,0 +1,105 @@
+//! Time source for searches limited by a wall-clock budget. Native builds read
+//! the system time, while in the browser `std::time` isn't available, so the
+//! time is taken from JS.
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = Date, js_name = now)]
+    fn date_now() -> f64;
+}
+
+/// Anything that can tell the current time in milliseconds. Only differences
+/// between two readings matter, so the starting point is up to the clock.
+pub trait Clock {
+    fn now_ms(&self) -> f64;
+}
+
+/// Clock reading the real time, wherever the code is running.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Clock for SystemClock {
+    fn now_ms(&self) -> f64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs_f64() * 1000.0)
+            .unwrap_or(0.0)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Clock for SystemClock {
+    fn now_ms(&self) -> f64 {
+        date_now()
+    }
+}
+
+/// Point in time after which a search has to stop.
+pub struct Deadline<'a> {
+    clock: &'a dyn Clock,
+    until_ms: f64,
+}
+
+impl<'a> Deadline<'a> {
+    /// Deadline `budget_ms` milliseconds from now.
+    pub fn init(clock: &'a dyn Clock, budget_ms: f64) -> Deadline<'a> {
+        Deadline {
+            clock,
+            until_ms: clock.now_ms() + budget_ms,
+        }
+    }
+
+    pub fn has_passed(&self) -> bool {
+        self.clock.now_ms() >= self.until_ms
+    }
+}
+
+/// Fake clock for tests, moving forward by a fixed step every time it's read.
+#[cfg(test)]
+pub struct FakeClock {
+    now: std::cell::Cell<f64>,
+    step_ms: f64,
+}
+
+#[cfg(test)]
+impl FakeClock {
+    pub fn init(step_ms: f64) -> FakeClock {
+        FakeClock {
+            now: std::cell::Cell::new(0.0),
+            step_ms,
+        }
+    }
+}
+
+#[cfg(test)]
+impl Clock for FakeClock {
+    fn now_ms(&self) -> f64 {
+        let now = self.now.get();
+        self.now.set(now + self.step_ms);
+        now
+    }
+}
+
+#[test]
+fn deadline_passes_once_budget_is_spent() {
+    let clock = FakeClock::init(10.0);
+    let deadline = Deadline::init(&clock, 25.0);
+
+    // Reads at 10 and 20 are within the budget, 30 isn't.
+    assert!(!deadline.has_passed());
+    assert!(!deadline.has_passed());
+    assert!(deadline.has_passed());
+}
+
+#[test]
+fn frozen_clock_never_runs_out() {
+    let clock = FakeClock::init(0.0);
+    let deadline = Deadline::init(&clock, 0.5);
+
+    assert!((0..1000).all(|_| !deadline.has_passed()));
+}
@@ -3,21 +3,19 @@ use std::io;
 // Use enums
 use crate::enums::board_column::*;
 use crate::enums::difficulty::Difficulty;
+use crate::enums::game_outcome::{is_game_over, GameOutcome};
 use crate::enums::player::{player_to_str, Player};
 
 // Use models
-use crate::models::board::{is_successful_move, Board, MoveSuccess};
-use crate::models::masks::get_cached_win_masks;
+use crate::models::board::{Board, Cell};
 
-use crate::minimax;
-
-/// Enum to describe possible move errors!
-/// TODO reuse exising type in the board model
-enum PlayerMoveError {
-    NoError,
-    UnknownColumn,
-    ColumnFull,
-}
+use crate::control::{CancelToken, Progress};
+use crate::engine::{engine_for_difficulty, Engine};
+use crate::error::{Connect4Error, IllegalMove};
+use crate::evaluation::{evaluate, EvalWeights, Weight};
+use crate::personality::Personality;
+#[cfg(test)]
+use crate::test_support::drawn_game;
 
 // Game struct
 #[derive(Debug, Clone)]
@@ -26,34 +24,89 @@ pub struct Connect4 {
     next: Player,
     board: Board,
     pub difficulty: Difficulty,
+    pub eval_weights: EvalWeights,
+    /// Seed for choosing between columns the AI finds equally good. With one,
+    /// the AI always plays the same move in the same position, otherwise it
+    /// picks randomly.
+    pub seed: Option<u64>,
+    /// How the AI picks between the columns it scored. Without one it always
+    /// plays the best.
+    pub personality: Option<Personality>,
 }
 
 impl Connect4 {
     /// Checks if the current player won the game!
     pub fn is_current_player_winner(&self) -> bool {
-        let player_status = self.board.get_status_num_for_player(&self.current);
+        self.board.is_winner(&self.current)
+    }
+
+    /// State of the game. Any player can be the winner, although normally
+    /// it's only ever the one who played last.
+    pub fn get_outcome(&self) -> GameOutcome {
+        if self.board.is_winner(&self.current) {
+            GameOutcome::Won(self.current, self.board.get_winning_tokens(&self.current))
+        } else if self.board.is_winner(&self.next) {
+            GameOutcome::Won(self.next, self.board.get_winning_tokens(&self.next))
+        } else if self.board.is_full() {
+            GameOutcome::Draw
+        } else {
+            GameOutcome::InProgress
+        }
+    }
+
+    /// Cells of all the lines the player won the game with, if any.
+    pub fn get_winning_lines(&self, player: &Player) -> Vec<Vec<Cell>> {
+        self.board.get_winning_lines(player)
+    }
+
+    /// Heuristic weight of the position for the current player, who has just
+    /// played, with the next player to move.
+    pub fn evaluate_for_current_player(&self) -> Weight {
+        evaluate(&self.board, &self.current, &self.next, &self.eval_weights)
+    }
+
+    /// Key identifying the position on the board and the player to move.
+    pub fn get_key(&self) -> u64 {
+        self.board.get_key(&self.current)
+    }
+
+    /// Key shared with the mirror image of the position, and whether it's the
+    /// key of the mirror image.
+    pub fn get_canonical_key(&self) -> (u64, bool) {
+        self.board.get_canonical_key(&self.current)
+    }
 
-        get_cached_win_masks()
-            .iter()
-            .fold(false, |acc, &m| acc || player_status & m == m)
+    pub fn get_current_player(&self) -> Player {
+        self.current
     }
 
     /// Switches players
     pub fn switch_players(&mut self) {
-        let new_next = self.current.clone();
-        self.current = self.next.clone();
-        self.next = new_next;
+        std::mem::swap(&mut self.current, &mut self.next);
     }
 
-    /// Drop a token in column
-    pub fn drop_token_in_col(&mut self, col_num: u8) -> MoveSuccess {
-        if let Option::Some(col) = num_to_col_num(col_num) {
-            self.board.drop_token(&self.current, col)
-        } else {
-            MoveSuccess::UnknownColumn
+    /// Drop a token in column, returning the index of the row it landed on.
+    /// Once someone has won, no more tokens can be dropped.
+    pub fn drop_token_in_col(&mut self, col_num: u8) -> Result<usize, Connect4Error> {
+        if self.board.is_winner(&self.current) || self.board.is_winner(&self.next) {
+            return Err(Connect4Error::GameOver);
+        }
+
+        match num_to_col_num(col_num) {
+            Option::Some(col) => self.board.drop_token(&self.current, col),
+            Option::None => Err(Connect4Error::IllegalMove {
+                col: col_num,
+                reason: IllegalMove::UnknownColumn,
+            }),
         }
     }
 
+    /// Takes back the token last dropped in the column, returning the row it
+    /// was on, or None if the column is empty. Players aren't switched back.
+    pub fn undo_drop_in_col(&mut self, col_num: u8) -> Option<usize> {
+        self.board.take_token(num_to_col_num(col_num)?)
+    }
+
     /// ...
     pub fn is_column_full(&self, col_num: u8) -> bool {
         if let Option::Some(col) = num_to_col_num(col_num) {
@@ -63,13 +116,43 @@ impl Connect4 {
         }
     }
 
-    pub fn get_ai_move(&mut self) -> (u8, bool) {
-        let col: u8 = minimax::play_for_ai(&self);
-        self.drop_token_in_col(col);
-        (col, self.is_current_player_winner())
+    /// Lets the AI play its move, and returns the column it played, along with
+    /// the outcome of the game after it. If the game was already over there's
+    /// no move to play.
+    pub fn get_ai_move(&mut self) -> (Option<u8>, GameOutcome) {
+        let mut engine = engine_for_difficulty(self.difficulty);
+        self.get_engine_move(engine.as_mut())
     }
 
-    pub fn get_board(self) -> Board {
+    /// Same as `get_ai_move`, but the column is chosen by the engine.
+    pub fn get_engine_move(&mut self, engine: &mut dyn Engine) -> (Option<u8>, GameOutcome) {
+        self.play_ai_move(|game| engine.choose_col(game))
+    }
+
+    /// Same as `get_engine_move`, but the search can be cancelled with the
+    /// token, and reports its progress as it goes, if the engine can.
+    pub fn get_engine_move_controlled(
+        &mut self,
+        engine: &mut dyn Engine,
+        cancel: Option<&CancelToken>,
+        on_progress: &mut dyn FnMut(&Progress),
+    ) -> (Option<u8>, GameOutcome) {
+        self.play_ai_move(|game| engine.choose_col_controlled(game, cancel, on_progress))
+    }
+
+    fn play_ai_move<F: FnOnce(&Connect4) -> Option<u8>>(
+        &mut self,
+        choose_col: F,
+    ) -> (Option<u8>, GameOutcome) {
+        if is_game_over(&self.get_outcome()) {
+            return (Option::None, self.get_outcome());
+        }
+
+        let col = choose_col(self).filter(|col_num| self.drop_token_in_col(*col_num).is_ok());
+        (col, self.get_outcome())
+    }
+
+    pub fn get_board(&self) -> Board {
         self.board
     }
 
@@ -77,20 +160,18 @@ impl Connect4 {
     pub fn init_from_input_for(
         current: Player,
         next: Player,
-        difficulty: String,
+        difficulty: Difficulty,
         str_board: Vec<Vec<String>>,
     ) -> Connect4 {
-        let board = Board::init_from(str_board);
+        let board = Board::init_from(&str_board);
         Connect4 {
             current,
             next,
             board,
-            difficulty: match difficulty.as_str() {
-                "easy" => Difficulty::Easy,
-                "medium" => Difficulty::Normal,
-                "hard" => Difficulty::Hard,
-                _ => Difficulty::Test,
-            },
+            difficulty,
+            eval_weights: EvalWeights::default(),
+            seed: Option::None,
+            personality: Option::None,
         }
     }
 
@@ -101,6 +182,93 @@ impl Connect4 {
             next: Player::AI,
             board: Board::init(),
             difficulty,
+            eval_weights: EvalWeights::default(),
+            seed: Option::None,
+            personality: Option::None,
         }
     }
+
+    /// Game after the moves, given as the columns played in turn, counted
+    /// from one, like "4453".
+    pub fn init_from_moves(difficulty: Difficulty, moves: &str) -> Result<Connect4, Connect4Error> {
+        let mut game = Connect4::init_vs_ai(difficulty);
+        moves
+            .chars()
+            .try_for_each(|col| -> Result<(), Connect4Error> {
+                // Anything but a digit isn't a column either.
+                game.drop_token_in_col(col.to_digit(10).map_or(0, |col| col as u8))?;
+                game.switch_players();
+                Ok(())
+            })?;
+        Ok(game)
+    }
+}
+
+#[test]
+fn game_knows_its_outcome() {
+    let mut game = Connect4::init_vs_ai(Difficulty::Test);
+    assert_eq!(game.get_outcome(), GameOutcome::InProgress);
+
+    [1, 1, 1, 1].iter().for_each(|col| {
+        game.drop_token_in_col(*col).unwrap();
+    });
+    assert_eq!(game.get_outcome(), GameOutcome::Won(Player::One, 0b1111));
+
+    // Winner isn't the current player any more, but it's still a win.
+    game.switch_players();
+    assert_eq!(game.get_outcome(), GameOutcome::Won(Player::One, 0b1111));
+}
+
+#[test]
+fn full_board_without_winner_is_a_draw() {
+    let mut game = drawn_game(Difficulty::Test, 0);
+
+    assert_eq!(game.get_outcome(), GameOutcome::Draw);
+    assert_eq!(game.get_ai_move(), (Option::None, GameOutcome::Draw));
+}
+
+#[test]
+fn illegal_moves_are_rejected() {
+    let mut game = Connect4::init_vs_ai(Difficulty::Test);
+    assert_eq!(
+        game.drop_token_in_col(8),
+        Err(Connect4Error::IllegalMove {
+            col: 8,
+            reason: IllegalMove::UnknownColumn
+        })
+    );
+
+    [2, 2, 2, 2, 2, 2, 1, 3, 1, 3, 1, 3].iter().for_each(|col| {
+        game.drop_token_in_col(*col).unwrap();
+        game.switch_players();
+    });
+    assert_eq!(
+        game.drop_token_in_col(2),
+        Err(Connect4Error::IllegalMove {
+            col: 2,
+            reason: IllegalMove::ColumnFull
+        })
+    );
+
+    game.drop_token_in_col(1).unwrap();
+    assert_eq!(game.drop_token_in_col(3), Err(Connect4Error::GameOver));
+}
+
+#[test]
+fn games_can_be_set_up_from_their_moves() {
+    let game = Connect4::init_from_moves(Difficulty::Test, "4453").unwrap();
+    assert_eq!(game.get_current_player(), Player::One);
+    assert_eq!(game.get_board().get_played_mask().count_ones(), 4);
+
+    assert_eq!(
+        Connect4::init_from_moves(Difficulty::Test, "44a").unwrap_err(),
+        Connect4Error::IllegalMove {
+            col: 0,
+            reason: IllegalMove::UnknownColumn
+        }
+    );
+    assert_eq!(
+        Connect4::init_from_moves(Difficulty::Test, "12121212").unwrap_err(),
+        Connect4Error::GameOver
+    );
 }
@@ -0,0 +1,66 @@
+//! Ways to follow and control a running search from the outside: a token to
+//! cancel it, and the progress it reports after every depth it finishes.
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use wasm_bindgen::prelude::*;
+
+/// Tells a running search to stop. Clones share the same flag, so one can be
+/// handed to the search, and the other kept to cancel it, even from another
+/// thread. Cancelled search still returns the best move it has found.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn init() -> CancelToken {
+        CancelToken::default()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Token API exposed to JS. In the browser the search blocks the thread it's
+/// running on, so the token is meant to be cancelled from the progress
+/// callback.
+#[wasm_bindgen]
+impl CancelToken {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> CancelToken {
+        CancelToken::init()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn cancelled(&self) -> bool {
+        self.is_cancelled()
+    }
+}
+
+/// How far the search got. Reported each time a depth is searched in full.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Progress {
+    pub depth: u8,
+    pub nodes: u64, // Searched in total, over all the depths so far
+    pub best_col: Option<u8>,
+}
+
+// Module tests!!
+
+#[test]
+fn clones_share_the_cancellation() {
+    let token = CancelToken::init();
+    let search_token = token.clone();
+    assert!(!search_token.is_cancelled());
+
+    std::thread::spawn(move || token.cancel()).join().unwrap();
+    assert!(search_token.is_cancelled());
+}
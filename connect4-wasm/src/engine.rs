@@ -0,0 +1,458 @@
+//! Engines choosing the moves of the AI. The game only knows about the
+//! `Engine` trait, so new bots are added by implementing it, and registering
+//! them under a name, without touching the game itself.
+//!
+//! Engines are selected the same way as difficulties, either by name, like
+//! `"greedy"`, or by an object with the name as its only field, holding the
+//! settings of the engine, like `{"mcts": {"iterations": 5000}}`.
+
+use crate::analysis::{analyze, analyze_to_depth, ColumnAnalysis};
+use crate::clock::SystemClock;
+use crate::connect4::Connect4;
+use crate::control::{CancelToken, Progress};
+use crate::enums::difficulty::Difficulty;
+use crate::error::Connect4Error;
+use crate::mcts::{self, MctsConfig};
+use crate::minimax::{
+    analyze_columns, play_for_ai_controlled, play_for_ai_with_table, select_column, tie_breaker,
+};
+use crate::models::board::COL_NUM;
+#[cfg(test)]
+use crate::test_support::game_after;
+use crate::transposition::TranspositionTable;
+use rand::seq::SliceRandom;
+use std::fmt;
+
+/// Something choosing the columns the AI plays. Analysis, pondering and
+/// personalities are optional, and engines without them keep the defaults.
+pub trait Engine: fmt::Debug {
+    /// Name the engine is registered under.
+    fn get_name(&self) -> &'static str;
+
+    /// Column to play for the side to move, counted from one. There's none
+    /// only if no column can be played.
+    fn choose_col(&mut self, game: &Connect4) -> Option<u8>;
+
+    /// Same as `choose_col`, but the search can be cancelled with the token,
+    /// in which case the engine plays the best column it found so far, and it
+    /// reports its progress as it goes, if it can.
+    fn choose_col_controlled(
+        &mut self,
+        game: &Connect4,
+        _cancel: Option<&CancelToken>,
+        _on_progress: &mut dyn FnMut(&Progress),
+    ) -> Option<u8> {
+        self.choose_col(game)
+    }
+
+    /// Scores of every column the side to move could play, if the engine can
+    /// tell how good they are.
+    fn analyze(&mut self, _game: &Connect4) -> Option<Vec<ColumnAnalysis>> {
+        Option::None
+    }
+
+    /// Thinks on the opponent's time, with them to move, so the engine's next
+    /// move comes quicker. Returns once the engine is done, or the token is
+    /// cancelled.
+    fn ponder(&mut self, _game: &Connect4, _cancel: Option<&CancelToken>) {}
+
+    /// Tells if the engine picks its column with the personality of the game,
+    /// out of every column it scored. Engines settling on a single column
+    /// can't play with a personality.
+    fn uses_personality(&self, _game: &Connect4) -> bool {
+        false
+    }
+}
+
+/// Builds an engine from its settings, which are null when it's selected by
+/// name.
+pub type EngineBuilder = fn(&serde_json::Value) -> Result<Box<dyn Engine>, Connect4Error>;
+
+/// Alpha-beta search, as deep as the difficulty of the game allows. Keeps its
+/// transposition table between moves, and fills it while pondering.
+#[derive(Debug)]
+pub struct MinimaxEngine {
+    table: TranspositionTable,
+}
+
+impl Default for MinimaxEngine {
+    fn default() -> MinimaxEngine {
+        MinimaxEngine::init()
+    }
+}
+
+impl MinimaxEngine {
+    pub fn init() -> MinimaxEngine {
+        MinimaxEngine {
+            table: TranspositionTable::init_default(),
+        }
+    }
+}
+
+impl Engine for MinimaxEngine {
+    fn get_name(&self) -> &'static str {
+        "minimax"
+    }
+
+    fn choose_col(&mut self, game: &Connect4) -> Option<u8> {
+        play_for_ai_with_table(game, &mut self.table)
+    }
+
+    fn choose_col_controlled(
+        &mut self,
+        game: &Connect4,
+        cancel: Option<&CancelToken>,
+        on_progress: &mut dyn FnMut(&Progress),
+    ) -> Option<u8> {
+        play_for_ai_controlled(game, &mut self.table, &SystemClock, cancel, on_progress)
+    }
+
+    fn analyze(&mut self, game: &Connect4) -> Option<Vec<ColumnAnalysis>> {
+        Option::Some(analyze(game, &mut self.table))
+    }
+
+    fn ponder(&mut self, game: &Connect4, cancel: Option<&CancelToken>) {
+        // Searching the opponent's move fills the table with the positions
+        // after every reply they could play.
+        play_for_ai_controlled(game, &mut self.table, &SystemClock, cancel, &mut |_| {});
+    }
+
+    /// On the MCTS difficulty, the search only comes up with its own column.
+    fn uses_personality(&self, game: &Connect4) -> bool {
+        !matches!(game.difficulty, Difficulty::Mcts(_))
+    }
+}
+
+/// Plays any column that isn't full.
+#[derive(Debug, Default)]
+pub struct RandomEngine;
+
+impl Engine for RandomEngine {
+    fn get_name(&self) -> &'static str {
+        "random"
+    }
+
+    fn choose_col(&mut self, game: &Connect4) -> Option<u8> {
+        let columns: Vec<u8> = (1..=COL_NUM as u8)
+            .filter(|col| !game.is_column_full(*col))
+            .collect();
+        columns.choose(&mut tie_breaker(game)).copied()
+    }
+}
+
+/// Plays the column with the best position right after it, without looking
+/// at any reply of the opponent.
+#[derive(Debug)]
+pub struct GreedyEngine {
+    table: TranspositionTable,
+}
+
+impl Default for GreedyEngine {
+    fn default() -> GreedyEngine {
+        GreedyEngine::init()
+    }
+}
+
+impl GreedyEngine {
+    pub fn init() -> GreedyEngine {
+        GreedyEngine {
+            table: TranspositionTable::init_default(),
+        }
+    }
+}
+
+impl Engine for GreedyEngine {
+    fn get_name(&self) -> &'static str {
+        "greedy"
+    }
+
+    fn choose_col(&mut self, game: &Connect4) -> Option<u8> {
+        select_column(game, analyze_columns(game, 0, &mut self.table))
+    }
+
+    fn analyze(&mut self, game: &Connect4) -> Option<Vec<ColumnAnalysis>> {
+        Option::Some(analyze_to_depth(game, 0, &mut self.table))
+    }
+
+    fn uses_personality(&self, _game: &Connect4) -> bool {
+        true
+    }
+}
+
+/// Monte Carlo tree search with the given settings.
+#[derive(Debug, Default)]
+pub struct MctsEngine {
+    config: MctsConfig,
+}
+
+impl MctsEngine {
+    pub fn init(config: MctsConfig) -> MctsEngine {
+        MctsEngine { config }
+    }
+}
+
+impl Engine for MctsEngine {
+    fn get_name(&self) -> &'static str {
+        "mcts"
+    }
+
+    fn choose_col(&mut self, game: &Connect4) -> Option<u8> {
+        self.choose_col_controlled(game, Option::None, &mut |_| {})
+    }
+
+    fn choose_col_controlled(
+        &mut self,
+        game: &Connect4,
+        cancel: Option<&CancelToken>,
+        _on_progress: &mut dyn FnMut(&Progress),
+    ) -> Option<u8> {
+        mcts::search(game, &self.config, &SystemClock, cancel).col
+    }
+}
+
+/// Engine playing at the difficulty, the one the AI plays with unless another
+/// one is selected.
+pub fn engine_for_difficulty(difficulty: Difficulty) -> Box<dyn Engine> {
+    match difficulty {
+        Difficulty::Mcts(config) => Box::new(MctsEngine::init(config)),
+        _ => Box::new(MinimaxEngine::init()),
+    }
+}
+
+/// Engines that can be selected, by name.
+#[derive(Debug, Clone)]
+pub struct EngineRegistry {
+    builders: Vec<(&'static str, EngineBuilder)>,
+}
+
+impl Default for EngineRegistry {
+    fn default() -> EngineRegistry {
+        EngineRegistry::init()
+    }
+}
+
+impl EngineRegistry {
+    /// Registry with the engines of the crate.
+    pub fn init() -> EngineRegistry {
+        EngineRegistry { builders: vec![] }
+            .with_engine("minimax", |settings| {
+                check_no_settings("minimax", settings)?;
+                Ok(Box::new(MinimaxEngine::init()))
+            })
+            .with_engine("random", |settings| {
+                check_no_settings("random", settings)?;
+                Ok(Box::new(RandomEngine))
+            })
+            .with_engine("greedy", |settings| {
+                check_no_settings("greedy", settings)?;
+                Ok(Box::new(GreedyEngine::init()))
+            })
+            .with_engine("mcts", build_mcts)
+    }
+
+    /// Registers the engine under the name, replacing any engine already
+    /// registered under it.
+    pub fn with_engine(mut self, name: &'static str, build: EngineBuilder) -> EngineRegistry {
+        self.builders.retain(|(registered, _)| *registered != name);
+        self.builders.push((name, build));
+        self
+    }
+
+    pub fn get_names(&self) -> Vec<&'static str> {
+        self.builders.iter().map(|(name, _)| *name).collect()
+    }
+
+    /// Builds the engine selected by the input, either its name, or an object
+    /// with the name as its only field, holding its settings.
+    pub fn create(&self, input: &serde_json::Value) -> Result<Box<dyn Engine>, Connect4Error> {
+        let selected = match input {
+            serde_json::Value::String(name) => Option::Some((name, &serde_json::Value::Null)),
+            serde_json::Value::Object(fields) if fields.len() == 1 => fields.iter().next(),
+            _ => Option::None,
+        };
+
+        match selected {
+            Option::Some((name, settings)) => self.create_by_name(name, settings),
+            Option::None => Err(Connect4Error::InvalidJson {
+                message: String::from("engine should be a name, or an object with a single field"),
+            }),
+        }
+    }
+
+    fn create_by_name(
+        &self,
+        name: &str,
+        settings: &serde_json::Value,
+    ) -> Result<Box<dyn Engine>, Connect4Error> {
+        self.builders
+            .iter()
+            .find(|(registered, _)| *registered == name)
+            .ok_or_else(|| Connect4Error::UnknownEngine {
+                name: String::from(name),
+            })
+            .and_then(|(_, build)| build(settings))
+    }
+}
+
+/// Refuses settings given to an engine that has none, rather than ignoring
+/// them.
+fn check_no_settings(name: &str, settings: &serde_json::Value) -> Result<(), Connect4Error> {
+    match settings {
+        serde_json::Value::Null => Ok(()),
+        serde_json::Value::Object(fields) if fields.is_empty() => Ok(()),
+        _ => Err(Connect4Error::UnknownEngine {
+            name: String::from(name),
+        }),
+    }
+}
+
+fn build_mcts(settings: &serde_json::Value) -> Result<Box<dyn Engine>, Connect4Error> {
+    let config = match settings {
+        serde_json::Value::Null => MctsConfig::default(),
+        settings => serde_json::from_value::<MctsConfig>(settings.clone())?,
+    };
+
+    if config.is_valid() {
+        Ok(Box::new(MctsEngine::init(config)))
+    } else {
+        Err(Connect4Error::UnknownEngine {
+            name: String::from("mcts"),
+        })
+    }
+}
+
+// Module tests!!
+
+#[test]
+fn engines_are_selected_by_name_or_settings() {
+    let registry = EngineRegistry::init();
+    let create = |json: &str| registry.create(&serde_json::from_str(json).unwrap());
+
+    assert_eq!(create(r#""greedy""#).unwrap().get_name(), "greedy");
+    assert_eq!(create(r#""mcts""#).unwrap().get_name(), "mcts");
+    assert_eq!(
+        create(r#"{"mcts":{"iterations":100}}"#).unwrap().get_name(),
+        "mcts"
+    );
+
+    assert_eq!(
+        create(r#""alphazero""#).unwrap_err(),
+        Connect4Error::UnknownEngine {
+            name: String::from("alphazero")
+        }
+    );
+    assert_eq!(
+        create(r#"{"mcts":{"iterations":0}}"#).unwrap_err().code(),
+        "unknown_engine"
+    );
+    assert_eq!(
+        create(r#"{"mcts":{},"random":{}}"#).unwrap_err().code(),
+        "invalid_json"
+    );
+    assert_eq!(create("3").unwrap_err().code(), "invalid_json");
+}
+
+#[test]
+fn engines_without_settings_refuse_any() {
+    let registry = EngineRegistry::init();
+    let create = |json: &str| registry.create(&serde_json::from_str(json).unwrap());
+
+    assert_eq!(create(r#"{"greedy":{}}"#).unwrap().get_name(), "greedy");
+    assert_eq!(
+        create(r#"{"minimax":{"depth":99}}"#).unwrap_err(),
+        Connect4Error::UnknownEngine {
+            name: String::from("minimax")
+        }
+    );
+    assert_eq!(
+        create(r#"{"random":3}"#).unwrap_err().code(),
+        "unknown_engine"
+    );
+}
+
+#[test]
+fn new_engines_can_be_registered() {
+    #[derive(Debug)]
+    struct LeftMost;
+
+    impl Engine for LeftMost {
+        fn get_name(&self) -> &'static str {
+            "left_most"
+        }
+
+        fn choose_col(&mut self, game: &Connect4) -> Option<u8> {
+            (1..=COL_NUM as u8).find(|col| !game.is_column_full(*col))
+        }
+    }
+
+    let registry = EngineRegistry::init().with_engine("left_most", |_| Ok(Box::new(LeftMost)));
+    assert_eq!(
+        registry.get_names(),
+        vec!["minimax", "random", "greedy", "mcts", "left_most"]
+    );
+
+    let mut engine = registry
+        .create(&serde_json::Value::String(String::from("left_most")))
+        .unwrap();
+    let mut game = game_after(Difficulty::Easy, "111111");
+    assert_eq!(game.get_engine_move(engine.as_mut()).0, Option::Some(2));
+
+    // Engines without analysis or pondering keep the defaults.
+    assert_eq!(engine.analyze(&game), Option::None);
+    engine.ponder(&game, Option::None);
+}
+
+#[test]
+fn every_engine_takes_a_win_in_one() {
+    // Player one has three in column 1, and it's their turn.
+    let game = game_after(Difficulty::Easy, "171716");
+
+    EngineRegistry::init()
+        .get_names()
+        .iter()
+        .filter(|name| **name != "random")
+        .for_each(|name| {
+            let mut engine = EngineRegistry::init()
+                .create(&serde_json::Value::String(String::from(*name)))
+                .unwrap();
+            assert_eq!(engine.choose_col(&game), Option::Some(1), "{}", name);
+        });
+}
+
+#[test]
+fn random_engine_plays_any_open_column() {
+    let mut game = game_after(Difficulty::Easy, "444444");
+    game.seed = Option::Some(1);
+    let mut engine = RandomEngine;
+
+    let played = engine.choose_col(&game);
+    assert!(played.is_some() && played != Option::Some(4));
+    assert_eq!(engine.choose_col(&game), played);
+}
+
+#[test]
+fn greedy_engine_only_looks_one_move_ahead() {
+    use crate::analysis::MoveValue;
+
+    // Player one has three in column 1, which the AI doesn't see coming.
+    let game = game_after(Difficulty::Easy, "17171");
+    let analysis = GreedyEngine::init().analyze(&game).unwrap();
+    assert_eq!(analysis.len(), 7);
+    assert!(analysis
+        .iter()
+        .all(|column| matches!(column.value, MoveValue::Heuristic { .. })));
+}
+
+#[test]
+fn minimax_engine_fills_its_table_while_pondering() {
+    let game = game_after(Difficulty::Easy, "4");
+    let mut engine = MinimaxEngine::init();
+    // Positions after the opponent's replies are cached.
+    engine.ponder(&game, Option::None);
+    assert!((1..=COL_NUM as u8).any(|col| {
+        let mut reply = game.clone();
+        reply.drop_token_in_col(col).unwrap();
+        reply.switch_players();
+        engine.table.get(reply.get_canonical_key().0).is_some()
+    }));
+}
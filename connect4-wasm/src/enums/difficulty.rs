@@ -1,10 +1,22 @@
-/// Type for determining difficulty
+//! Type for determining difficulty
+
+use crate::error::Connect4Error;
+use crate::mcts::MctsConfig;
+use crate::models::board::{COL_NUM, ROW_NUM};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
 
 /**
  * Different difficulties of the game! Each difficulty corresponds to the
  * depth to which the minimax algorithm will look ahead for possible outcomes.
+ * The depth can also be given directly, or the AI can be given a time budget
+ * instead, in which case it looks as deep as it manages to in time. On the
+ * perfect difficulty the AI solves the position, however long it takes, and
+ * plays one of the best moves, unless its personality strays from them.
+ * With MCTS the AI plays with Monte Carlo tree search instead of minimax.
  */
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "DifficultyInput", into = "DifficultyInput")]
 pub enum Difficulty {
     Test,
     Easy,
@@ -12,8 +24,94 @@ pub enum Difficulty {
     Hard,
     VeryHard,
     NoChance,
+    Perfect,
+    Depth(u8),
+    TimeBudget(f64), // In milliseconds
+    Mcts(MctsConfig),
+}
+
+/// Difficulty as it comes in the JSON input. It's either the name of one of
+/// the difficulties, a depth as a plain number, or an object with either the
+/// `depth`, the `time_ms`, or the `mcts` field, holding the settings of the
+/// MCTS search. Depths can't go past the number of cells on the board.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DifficultyInput {
+    Name(String),
+    Depth(u8),
+    DepthConfig { depth: u8 },
+    TimeBudget { time_ms: f64 },
+    Mcts { mcts: MctsConfig },
+}
+
+impl TryFrom<DifficultyInput> for Difficulty {
+    type Error = Connect4Error;
+
+    fn try_from(input: DifficultyInput) -> Result<Difficulty, Connect4Error> {
+        match input {
+            DifficultyInput::Name(name) => str_to_difficulty(&name),
+            DifficultyInput::Depth(depth) | DifficultyInput::DepthConfig { depth }
+                if depth <= (ROW_NUM * COL_NUM) as u8 =>
+            {
+                Ok(Difficulty::Depth(depth))
+            }
+            DifficultyInput::TimeBudget { time_ms } if time_ms.is_finite() && time_ms > 0.0 => {
+                Ok(Difficulty::TimeBudget(time_ms))
+            }
+            DifficultyInput::Mcts { mcts } if mcts.is_valid() => Ok(Difficulty::Mcts(mcts)),
+            DifficultyInput::Mcts { .. } => Err(Connect4Error::UnknownDifficulty {
+                name: String::from("mcts"),
+            }),
+            DifficultyInput::Depth(depth) | DifficultyInput::DepthConfig { depth } => {
+                Err(Connect4Error::UnknownDifficulty {
+                    name: format!("depth {}", depth),
+                })
+            }
+            DifficultyInput::TimeBudget { time_ms } => Err(Connect4Error::UnknownDifficulty {
+                name: format!("{}ms", time_ms),
+            }),
+        }
+    }
 }
 
+impl From<Difficulty> for DifficultyInput {
+    fn from(difficulty: Difficulty) -> DifficultyInput {
+        match difficulty {
+            Difficulty::Test => DifficultyInput::Name(String::from("test")),
+            Difficulty::Easy => DifficultyInput::Name(String::from("easy")),
+            Difficulty::Normal => DifficultyInput::Name(String::from("normal")),
+            Difficulty::Hard => DifficultyInput::Name(String::from("hard")),
+            Difficulty::VeryHard => DifficultyInput::Name(String::from("very_hard")),
+            Difficulty::NoChance => DifficultyInput::Name(String::from("no_chance")),
+            Difficulty::Perfect => DifficultyInput::Name(String::from("perfect")),
+            Difficulty::Depth(depth) => DifficultyInput::Depth(depth),
+            Difficulty::TimeBudget(time_ms) => DifficultyInput::TimeBudget { time_ms },
+            Difficulty::Mcts(mcts) => DifficultyInput::Mcts { mcts },
+        }
+    }
+}
+
+/// Difficulty with the given name. The UI calls the normal difficulty
+/// "medium", so that's accepted as well.
+pub fn str_to_difficulty(name: &str) -> Result<Difficulty, Connect4Error> {
+    match name {
+        "test" => Ok(Difficulty::Test),
+        "easy" => Ok(Difficulty::Easy),
+        "normal" | "medium" => Ok(Difficulty::Normal),
+        "hard" => Ok(Difficulty::Hard),
+        "very_hard" => Ok(Difficulty::VeryHard),
+        "no_chance" => Ok(Difficulty::NoChance),
+        "perfect" => Ok(Difficulty::Perfect),
+        "mcts" => Ok(Difficulty::Mcts(MctsConfig::default())),
+        _ => Err(Connect4Error::UnknownDifficulty {
+            name: String::from(name),
+        }),
+    }
+}
+
+/// Depth of the search. With a time budget, or when solving the game, the
+/// depth is only limited by the number of cells on the board. MCTS doesn't
+/// search to a depth, so its analysis goes as deep as the hardest difficulty.
 pub fn difficulty_to_depth(difficulty: Difficulty) -> u8 {
     match difficulty {
         Difficulty::Test => 1,
@@ -21,6 +119,102 @@ pub fn difficulty_to_depth(difficulty: Difficulty) -> u8 {
         Difficulty::Normal => 3,
         Difficulty::Hard => 5,
         Difficulty::VeryHard => 6,
-        Difficulty::NoChance => 10,
+        Difficulty::NoChance | Difficulty::Mcts(_) => 10,
+        Difficulty::Depth(depth) => depth,
+        Difficulty::Perfect | Difficulty::TimeBudget(_) => (ROW_NUM * COL_NUM) as u8,
+    }
+}
+
+/// Time the AI has to think, if the difficulty limits it.
+pub fn difficulty_to_time_budget(difficulty: Difficulty) -> Option<f64> {
+    match difficulty {
+        Difficulty::TimeBudget(budget_ms) => Option::Some(budget_ms),
+        _ => Option::None,
     }
 }
+
+/// Tells if the AI plays the moves of the opening book, when the position is
+/// in it. Weaker difficulties search for their own moves, so they can still
+/// be beaten in the opening.
+pub fn difficulty_uses_book(difficulty: Difficulty) -> bool {
+    matches!(
+        difficulty,
+        Difficulty::NoChance | Difficulty::Perfect | Difficulty::TimeBudget(_)
+    )
+}
+
+// Module tests!!
+
+#[test]
+fn can_deserialize_every_difficulty() {
+    let parse = |json: &str| serde_json::from_str::<Difficulty>(json).unwrap();
+
+    assert_eq!(parse(r#""test""#), Difficulty::Test);
+    assert_eq!(parse(r#""easy""#), Difficulty::Easy);
+    assert_eq!(parse(r#""normal""#), Difficulty::Normal);
+    assert_eq!(parse(r#""medium""#), Difficulty::Normal);
+    assert_eq!(parse(r#""hard""#), Difficulty::Hard);
+    assert_eq!(parse(r#""very_hard""#), Difficulty::VeryHard);
+    assert_eq!(parse(r#""no_chance""#), Difficulty::NoChance);
+    assert_eq!(parse(r#""perfect""#), Difficulty::Perfect);
+    assert_eq!(parse("4"), Difficulty::Depth(4));
+    assert_eq!(parse(r#"{"depth":7}"#), Difficulty::Depth(7));
+    assert_eq!(parse(r#"{"time_ms":250}"#), Difficulty::TimeBudget(250.0));
+    assert_eq!(parse(r#""mcts""#), Difficulty::Mcts(MctsConfig::default()));
+    assert_eq!(
+        parse(r#"{"mcts":{"iterations":800}}"#),
+        Difficulty::Mcts(MctsConfig {
+            iterations: Option::Some(800),
+            ..MctsConfig::default()
+        })
+    );
+}
+
+#[test]
+fn rejects_unknown_difficulties() {
+    assert_eq!(
+        Difficulty::try_from(DifficultyInput::Name(String::from("hrad"))),
+        Err(Connect4Error::UnknownDifficulty {
+            name: String::from("hrad")
+        })
+    );
+    assert!(Difficulty::try_from(DifficultyInput::TimeBudget { time_ms: -1.0 }).is_err());
+    assert_eq!(
+        Difficulty::try_from(DifficultyInput::Depth(43)),
+        Err(Connect4Error::UnknownDifficulty {
+            name: String::from("depth 43")
+        })
+    );
+    assert_eq!(
+        Difficulty::try_from(DifficultyInput::DepthConfig { depth: 42 }),
+        Ok(Difficulty::Depth(42))
+    );
+    assert!(serde_json::from_str::<Difficulty>(r#"{"depth":200}"#).is_err());
+    assert!(serde_json::from_str::<Difficulty>(r#"{"mcts":{"exploration":-2}}"#).is_err());
+
+    assert!(serde_json::from_str::<Difficulty>(r#""hrad""#).is_err());
+    assert!(serde_json::from_str::<Difficulty>("-3").is_err());
+    assert!(serde_json::from_str::<Difficulty>(r#"{"level":3}"#).is_err());
+}
+
+#[test]
+fn difficulties_survive_a_round_trip() {
+    [
+        Difficulty::Test,
+        Difficulty::VeryHard,
+        Difficulty::Depth(4),
+        Difficulty::TimeBudget(120.0),
+        Difficulty::Mcts(MctsConfig {
+            time_ms: Option::Some(80.0),
+            ..MctsConfig::default()
+        }),
+    ]
+    .iter()
+    .for_each(|difficulty| {
+        let json = serde_json::to_string(difficulty).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Difficulty>(&json).unwrap(),
+            *difficulty
+        );
+    });
+}
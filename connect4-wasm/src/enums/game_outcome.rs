@@ -0,0 +1,18 @@
+//! State of the game, as seen after the last move.
+
+use crate::enums::player::Player;
+use crate::models::board::Bitboard;
+
+/// Game is either still going, won by one of the players, or drawn once the
+/// board fills up without anyone connecting four. A win carries the bitboard
+/// of the tokens making up the winning line(s).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameOutcome {
+    InProgress,
+    Won(Player, Bitboard),
+    Draw,
+}
+
+pub fn is_game_over(outcome: &GameOutcome) -> bool {
+    !matches!(outcome, GameOutcome::InProgress)
+}
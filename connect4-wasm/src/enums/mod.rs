@@ -1,3 +1,4 @@
 pub mod board_column;
 pub mod difficulty;
+pub mod game_outcome;
 pub mod player;
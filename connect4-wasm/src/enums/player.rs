@@ -1,6 +1,6 @@
 /// Player enum
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Player {
     One,
     Two,
@@ -14,3 +14,12 @@ pub fn player_to_str(player: &Player) -> String {
         Player::AI => String::from("AI"),
     }
 }
+
+/// Name of the player as used in the JSON exchanged with the UI.
+pub fn player_to_json_str(player: &Player) -> &'static str {
+    match player {
+        Player::One => "player",
+        Player::Two => "two",
+        Player::AI => "ai",
+    }
+}
@@ -0,0 +1,149 @@
+//! Errors returned by the crate. Every error has a stable code, which is what
+//! the JS side should match on, as the messages can change.
+
+use crate::validation::Diagnostic;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Reason a token can't be dropped in a column.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IllegalMove {
+    UnknownColumn,
+    ColumnFull,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum Connect4Error {
+    /// Input couldn't be parsed.
+    InvalidJson { message: String },
+    /// Board in the input isn't one that can come up in a game.
+    BadShape { diagnostics: Vec<Diagnostic> },
+    /// Column, counted from one, can't be played.
+    IllegalMove { col: u8, reason: IllegalMove },
+    /// Someone has already won, so no more moves can be played.
+    GameOver,
+    /// It's the other player's turn.
+    WrongTurn { expected: String, found: String },
+    /// Difficulty isn't one of the known names.
+    UnknownDifficulty { name: String },
+    /// Engine isn't one of the registered ones, or its settings are wrong.
+    UnknownEngine { name: String },
+    /// Personality isn't one of the presets, or its settings are wrong.
+    UnknownPersonality { name: String },
+    /// Engine settles on a single column, so there's nothing for the
+    /// personality to pick between.
+    UnsupportedPersonality { engine: String },
+}
+
+impl Connect4Error {
+    /// Turns problems found with the board into an error. Problems with whose
+    /// turn it is, or the game being over, get their own errors.
+    pub fn from_diagnostics(diagnostics: Vec<Diagnostic>) -> Connect4Error {
+        match diagnostics.as_slice() {
+            [Diagnostic::WrongSideToMove { expected, found }] => Connect4Error::WrongTurn {
+                expected: expected.clone(),
+                found: found.clone(),
+            },
+            [Diagnostic::GameAlreadyOver { .. }] => Connect4Error::GameOver,
+            _ => Connect4Error::BadShape { diagnostics },
+        }
+    }
+
+    /// Stable code of the error, the same one it's tagged with in the JSON.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Connect4Error::InvalidJson { .. } => "invalid_json",
+            Connect4Error::BadShape { .. } => "bad_shape",
+            Connect4Error::IllegalMove { .. } => "illegal_move",
+            Connect4Error::GameOver => "game_over",
+            Connect4Error::WrongTurn { .. } => "wrong_turn",
+            Connect4Error::UnknownDifficulty { .. } => "unknown_difficulty",
+            Connect4Error::UnknownEngine { .. } => "unknown_engine",
+            Connect4Error::UnknownPersonality { .. } => "unknown_personality",
+            Connect4Error::UnsupportedPersonality { .. } => "unsupported_personality",
+        }
+    }
+
+    /// Error as JSON, to be passed on to the JS side.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| format!(r#"{{"code":"{}"}}"#, self.code()))
+    }
+}
+
+impl fmt::Display for Connect4Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Connect4Error::InvalidJson { message } => write!(f, "invalid JSON: {}", message),
+            Connect4Error::BadShape { diagnostics } => {
+                write!(f, "invalid board, {} problem(s) found", diagnostics.len())
+            }
+            Connect4Error::IllegalMove { col, reason } => match reason {
+                IllegalMove::UnknownColumn => write!(f, "there's no column {}", col),
+                IllegalMove::ColumnFull => write!(f, "column {} is full", col),
+            },
+            Connect4Error::GameOver => write!(f, "game is already over"),
+            Connect4Error::WrongTurn { expected, found } => {
+                write!(
+                    f,
+                    "expected {} to move, but it's {}'s turn",
+                    expected, found
+                )
+            }
+            Connect4Error::UnknownDifficulty { name } => write!(f, "unknown difficulty {}", name),
+            Connect4Error::UnknownEngine { name } => write!(f, "unknown engine {}", name),
+            Connect4Error::UnknownPersonality { name } => {
+                write!(f, "unknown personality {}", name)
+            }
+            Connect4Error::UnsupportedPersonality { engine } => {
+                write!(f, "engine {} can't play with a personality", engine)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Connect4Error {}
+
+impl From<serde_json::Error> for Connect4Error {
+    fn from(err: serde_json::Error) -> Connect4Error {
+        Connect4Error::InvalidJson {
+            message: err.to_string(),
+        }
+    }
+}
+
+// Module tests!!
+
+#[test]
+fn errors_serialize_with_their_code() {
+    let err = Connect4Error::IllegalMove {
+        col: 3,
+        reason: IllegalMove::ColumnFull,
+    };
+    assert_eq!(
+        err.to_json(),
+        r#"{"code":"illegal_move","col":3,"reason":"column_full"}"#
+    );
+    assert_eq!(Connect4Error::GameOver.to_json(), r#"{"code":"game_over"}"#);
+
+    let err = Connect4Error::from(serde_json::from_str::<u8>("nope").unwrap_err());
+    assert_eq!(err.code(), "invalid_json");
+}
+
+#[test]
+fn turn_and_game_over_diagnostics_get_their_own_codes() {
+    let err = Connect4Error::from_diagnostics(vec![Diagnostic::WrongSideToMove {
+        expected: String::from("ai"),
+        found: String::from("player"),
+    }]);
+    assert_eq!(err.code(), "wrong_turn");
+
+    let err = Connect4Error::from_diagnostics(vec![Diagnostic::GameAlreadyOver {
+        winner: String::from("ai"),
+    }]);
+    assert_eq!(err, Connect4Error::GameOver);
+
+    let err = Connect4Error::from_diagnostics(vec![Diagnostic::TwoWinners]);
+    assert_eq!(err.code(), "bad_shape");
+}
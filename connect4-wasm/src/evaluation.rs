@@ -0,0 +1,217 @@
+//! Heuristic evaluation of positions where the search has to stop before the
+//! game is decided.
+
+use crate::enums::player::Player;
+use crate::models::board::{Bitboard, Board, BOARD_MASK, COL_HEIGHT, COL_NUM, ROW_NUM};
+
+/// Type of the weights given to moves and positions by the search.
+pub type Weight = i16;
+
+/// Heuristic weights always stay below it, so they're never mistaken for a
+/// win, which the search scores on its own.
+pub const WIN_WEIGHT: Weight = 1000;
+
+/// Column in the middle of the board, part of the most lines.
+const CENTRE_COL: usize = 3;
+
+/// How much each feature of a position is worth. Weights are given per
+/// occurrence, and the player's features are counted against the opponent's.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EvalWeights {
+    /// Token in the centre column.
+    pub centre_column: Weight,
+    /// Empty cell that would complete four in a row, for one player only.
+    pub open_three: Weight,
+    /// Empty cell that would complete four in a row for both players. Counted
+    /// for the player whose parity matches the row of the cell.
+    pub shared_threat: Weight,
+    /// Open three completed on a row matching the player's parity. The player
+    /// moving first gets odd rows (counting from one at the bottom), and their
+    /// opponent even rows, as those are the cells they usually end up playing.
+    pub good_parity_threat: Weight,
+}
+
+impl Default for EvalWeights {
+    fn default() -> EvalWeights {
+        EvalWeights {
+            centre_column: 3,
+            open_three: 5,
+            shared_threat: 4,
+            good_parity_threat: 8,
+        }
+    }
+}
+
+/// Evaluates the position from the point of view of the player, with the
+/// other player being next to move. Positive weights are good for the player.
+/// The weight always stays below the weight of a win.
+pub fn evaluate(board: &Board, player: &Player, to_move: &Player, weights: &EvalWeights) -> Weight {
+    let mine = board.get_tokens_for_player(player);
+    let theirs = board.get_opponent_tokens(player);
+    let played = board.get_played_mask();
+
+    // First player is always the one to move when there's an even number of
+    // tokens on the board.
+    let moved_first = played.count_ones().is_multiple_of(2) == (player == to_move);
+    let (my_rows, their_rows) = if moved_first {
+        (odd_rows_mask(), !odd_rows_mask() & BOARD_MASK)
+    } else {
+        (!odd_rows_mask() & BOARD_MASK, odd_rows_mask())
+    };
+
+    let my_threats = winning_cells(mine, played);
+    let their_threats = winning_cells(theirs, played);
+    let shared = my_threats & their_threats;
+
+    let centre = count(mine & column_mask(CENTRE_COL)) - count(theirs & column_mask(CENTRE_COL));
+    let open_threes = count(my_threats & !shared) - count(their_threats & !shared);
+    let parity_threats =
+        count(my_threats & !shared & my_rows) - count(their_threats & !shared & their_rows);
+    let shared_threats = count(shared & my_rows) - count(shared & their_rows);
+
+    let weight = centre * weights.centre_column
+        + open_threes * weights.open_three
+        + parity_threats * weights.good_parity_threat
+        + shared_threats * weights.shared_threat;
+
+    weight.clamp(-WIN_WEIGHT + 1, WIN_WEIGHT - 1)
+}
+
+/// Empty cells on the board which would complete four in a row for the tokens.
+pub fn winning_cells(tokens: Bitboard, played: Bitboard) -> Bitboard {
+    // Vertical, only possible with three tokens right below.
+    let vertical = (tokens << 1) & (tokens << 2) & (tokens << 3);
+
+    [COL_HEIGHT - 1, COL_HEIGHT, COL_HEIGHT + 1]
+        .iter()
+        .fold(vertical, |cells, &dir| {
+            // Two tokens on one side, and one or two on the other.
+            let before = (tokens << dir) & (tokens << (2 * dir));
+            let after = (tokens >> dir) & (tokens >> (2 * dir));
+
+            cells
+                | (before & (tokens << (3 * dir)))
+                | (before & (tokens >> dir))
+                | (after & (tokens << dir))
+                | (after & (tokens >> (3 * dir)))
+        })
+        & BOARD_MASK
+        & !played
+}
+
+fn count(bitboard: Bitboard) -> Weight {
+    bitboard.count_ones() as Weight
+}
+
+pub fn column_mask(col: usize) -> Bitboard {
+    ((1 << ROW_NUM) - 1) << (col * COL_HEIGHT as usize)
+}
+
+/// Cells on the first, third and fifth row.
+fn odd_rows_mask() -> Bitboard {
+    let rows = 0b010101;
+    (0..COL_NUM as usize).fold(0, |mask, col| mask | rows << (col * COL_HEIGHT as usize))
+}
+
+// Module tests!!
+
+#[cfg(test)]
+fn board_from(moves: &[u8]) -> Board {
+    use crate::enums::board_column::num_to_col_num;
+
+    moves
+        .iter()
+        .enumerate()
+        .fold(Board::init(), |mut board, (i, col)| {
+            let player = if i % 2 == 0 { Player::One } else { Player::AI };
+            board
+                .drop_token(&player, num_to_col_num(*col).unwrap())
+                .unwrap();
+            board
+        })
+}
+
+#[test]
+fn centre_is_worth_more_than_the_edge() {
+    let weights = EvalWeights::default();
+
+    // Player one just played, so the AI is next.
+    let centre = evaluate(&board_from(&[4]), &Player::One, &Player::AI, &weights);
+    let edge = evaluate(&board_from(&[1]), &Player::One, &Player::AI, &weights);
+
+    assert!(centre > edge);
+    assert_eq!(centre, weights.centre_column);
+    assert_eq!(edge, 0);
+}
+
+#[test]
+fn evaluation_is_symmetric() {
+    let weights = EvalWeights::default();
+    let board = board_from(&[4, 4, 3, 5, 2, 2, 6]);
+
+    assert_eq!(
+        evaluate(&board, &Player::One, &Player::AI, &weights),
+        -evaluate(&board, &Player::AI, &Player::AI, &weights)
+    );
+}
+
+#[test]
+fn finds_cells_completing_four() {
+    // Player one has three in a row at the bottom, columns 2 to 4.
+    let board = board_from(&[2, 2, 3, 3, 4]);
+    let mine = board.get_tokens_for_player(&Player::One);
+    let cells = winning_cells(mine, board.get_played_mask());
+
+    assert_eq!(cells, 1 | 1 << (4 * COL_HEIGHT));
+
+    // Vertical three only has the cell on top.
+    let board = board_from(&[1, 2, 1, 2, 1]);
+    let mine = board.get_tokens_for_player(&Player::One);
+    assert_eq!(winning_cells(mine, board.get_played_mask()), 1 << 3);
+
+    // Gaps in the middle of a line count as well.
+    let board = board_from(&[1, 1, 2, 2, 4]);
+    let mine = board.get_tokens_for_player(&Player::One);
+    assert_eq!(
+        winning_cells(mine, board.get_played_mask()),
+        1 << (2 * COL_HEIGHT)
+    );
+}
+
+#[test]
+fn counts_open_threes_and_their_parity() {
+    let weights = EvalWeights {
+        centre_column: 0,
+        open_three: 1,
+        shared_threat: 0,
+        good_parity_threat: 10,
+    };
+
+    // Player one has two threats on the bottom (odd) row, which is their row.
+    let board = board_from(&[2, 2, 3, 3, 4]);
+    assert_eq!(evaluate(&board, &Player::One, &Player::AI, &weights), 22);
+
+    // Similar threats on the second (even) row don't get the bonus.
+    let board = board_from(&[3, 2, 2, 4, 3, 7, 4]);
+    assert_eq!(evaluate(&board, &Player::One, &Player::AI, &weights), 2);
+}
+
+#[test]
+fn shared_threats_go_to_the_player_with_parity() {
+    let weights = EvalWeights {
+        centre_column: 0,
+        open_three: 0,
+        shared_threat: 1,
+        good_parity_threat: 0,
+    };
+
+    // Both players need the bottom cell of column 4 to complete their row.
+    let board = board_from(&[1, 5, 2, 6, 3, 7]);
+    let played = board.get_played_mask();
+    let shared = winning_cells(board.get_tokens_for_player(&Player::One), played)
+        & winning_cells(board.get_tokens_for_player(&Player::AI), played);
+
+    assert_eq!(shared, 1 << (3 * COL_HEIGHT));
+    assert_eq!(evaluate(&board, &Player::One, &Player::One, &weights), 1);
+    assert_eq!(evaluate(&board, &Player::AI, &Player::One, &weights), -1);
+}
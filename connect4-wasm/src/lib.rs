@@ -3,59 +3,410 @@
 #![allow(unused_imports)]
 
 /// List of top level modules!
+mod analysis;
+mod benchmark;
+mod book;
+#[cfg(feature = "callbacks")]
+mod callbacks;
+mod clock;
 mod connect4;
+mod control;
+mod engine;
 mod enums;
+mod error;
+mod evaluation;
+mod mcts;
 mod minimax;
 mod models;
+mod ordering;
+mod personality;
+mod score;
+mod session;
+mod solver;
+#[cfg(test)]
+mod test_support;
+mod transposition;
+mod validation;
+mod worker;
 
 /// Using Connect4 module in the main only!
+use crate::analysis::ColumnAnalysis;
 use crate::connect4::Connect4;
+use crate::engine::EngineRegistry;
+use crate::enums::difficulty::{Difficulty, DifficultyInput};
+use crate::enums::game_outcome::GameOutcome;
 use crate::enums::player::Player;
+use crate::error::Connect4Error;
+use crate::models::board::Board;
+use crate::personality::{Personality, PersonalityInput};
+use crate::session::{outcome_to_output, GameSession};
 use serde::{Deserialize, Serialize};
-use serde_json::{Result, Value};
+use std::convert::TryFrom;
 
 /// WASM
 use wasm_bindgen::prelude::*;
 
-#[wasm_bindgen]
-extern "C" {
-    pub fn connect4_ai_move(c: u8, w: bool);
-    pub fn connect4_player_win(w: bool);
-    pub fn connect4_err(err: &str);
-}
+/// Used by the `node_counts` example, comparing the search with and without
+/// move ordering.
+pub use crate::benchmark::{count_nodes_by_difficulty, NodeCount, BENCHMARK_POSITIONS};
+/// Used by the `versus` example, playing games between two difficulties.
+pub use crate::benchmark::{play_match, MatchResult};
+/// Used by the `generate_book` example, to generate the opening book.
+pub use crate::book::generate_book;
 
 #[derive(Serialize, Deserialize)]
 struct Input {
-    difficulty: String,
+    difficulty: DifficultyInput,
     player: String,
     board: Vec<Vec<String>>,
+    /// Makes the AI play the same move every time for the same board.
+    #[serde(default)]
+    seed: Option<u64>,
+    /// Engine the AI plays with, instead of the one of the difficulty.
+    #[serde(default)]
+    engine: Option<serde_json::Value>,
+    /// How the AI picks between the columns it scored, to play like a person.
+    #[serde(default)]
+    personality: Option<PersonalityInput>,
+}
+
+/// Parses the input into a session, with the AI to move, which is how the
+/// board is always sent.
+fn parse_input(data: &str) -> Result<GameSession, Connect4Error> {
+    parse_input_with(data, |_| Player::AI)
+}
+
+/// Parses the input into a session, with whoever's turn it is on the board to
+/// move.
+fn parse_input_for_side_to_move(data: &str) -> Result<GameSession, Connect4Error> {
+    parse_input_with(data, Board::get_side_to_move)
+}
+
+/// Parses the input into a session, with the player `to_move` picks for the
+/// board to move.
+fn parse_input_with(
+    data: &str,
+    to_move: fn(&Board) -> Player,
+) -> Result<GameSession, Connect4Error> {
+    let inpt = serde_json::from_str::<Input>(data)?;
+    let difficulty = Difficulty::try_from(inpt.difficulty)?;
+    let to_move = to_move(&Board::init_from(&inpt.board));
+    let mut session = GameSession::init_from_board(difficulty, inpt.board, to_move)?;
+    session.set_seed(inpt.seed);
+    if let Option::Some(engine) = inpt.engine {
+        session.select_engine(&EngineRegistry::init(), engine)?;
+    }
+    if let Option::Some(personality) = inpt.personality {
+        session.set_personality(Option::Some(Personality::try_from(personality)?))?;
+    }
+    Ok(session)
 }
 
+/// Result of a call from JS. Either there's an error, or the outcome of the
+/// game, along with the column the AI played, if it was its move. Outcome and
+/// error are both JSON.
 #[wasm_bindgen]
-pub fn process_data_for_ai(data: &str) {
-    match serde_json::from_str::<Input>(data) {
-        Ok(inpt) => {
-            let mut game: Connect4 =
-                Connect4::init_from_input_for(Player::AI, Player::One, inpt.difficulty, inpt.board);
-            let (col, is_winner) = game.get_ai_move();
+#[derive(Debug, Clone, PartialEq)]
+pub struct MoveResult {
+    col: Option<u8>,
+    won: bool,
+    outcome: Option<String>,
+    error: Option<String>,
+}
 
-            // Report data...
-            connect4_ai_move(col, is_winner);
+impl MoveResult {
+    fn from_game(col: Option<u8>, player: Player, game: &Connect4) -> MoveResult {
+        MoveResult {
+            col,
+            won: matches!(game.get_outcome(), GameOutcome::Won(winner, _) if winner == player),
+            outcome: serde_json::to_string(&outcome_to_output(game)).ok(),
+            error: Option::None,
         }
-        Err(err) => connect4_err(err.to_string().as_str()),
-    };
+    }
+
+    fn from_error(err: &Connect4Error) -> MoveResult {
+        MoveResult {
+            col: Option::None,
+            won: false,
+            outcome: Option::None,
+            error: Option::Some(err.to_json()),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl MoveResult {
+    /// Column the AI played, counted from one.
+    #[wasm_bindgen(getter)]
+    pub fn col(&self) -> Option<u8> {
+        self.col
+    }
+
+    /// Tells if the player who just moved has won.
+    #[wasm_bindgen(getter)]
+    pub fn won(&self) -> bool {
+        self.won
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn outcome(&self) -> Option<String> {
+        self.outcome.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn error(&self) -> Option<String> {
+        self.error.clone()
+    }
+}
+
+/// Lets the AI play on the board from the input. There's no column in the
+/// result when the game was already over.
+#[wasm_bindgen]
+pub fn ai_move(data: &str) -> MoveResult {
+    match parse_input(data) {
+        Ok(mut session) => {
+            let col = session.play_ai().ok().map(|(col, _)| col);
+            MoveResult::from_game(col, Player::AI, session.get_game())
+        }
+        Err(err) => MoveResult::from_error(&err),
+    }
+}
+
+/// Checks the board from the input after the player has made their move.
+#[wasm_bindgen]
+pub fn player_move(data: &str) -> MoveResult {
+    match parse_input(data) {
+        Ok(session) => MoveResult::from_game(Option::None, Player::One, session.get_game()),
+        Err(err) => MoveResult::from_error(&err),
+    }
+}
+
+/// Result of analysing a board from JS. Either there's an error, or the score
+/// of every column the side to move could play. Both are JSON.
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalysisResult {
+    columns: Option<String>,
+    error: Option<String>,
+}
+
+impl AnalysisResult {
+    fn from_analysis(analysis: &[ColumnAnalysis]) -> AnalysisResult {
+        AnalysisResult {
+            columns: serde_json::to_string(analysis).ok(),
+            error: Option::None,
+        }
+    }
+
+    fn from_error(err: &Connect4Error) -> AnalysisResult {
+        AnalysisResult {
+            columns: Option::None,
+            error: Option::Some(err.to_json()),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl AnalysisResult {
+    #[wasm_bindgen(getter)]
+    pub fn columns(&self) -> Option<String> {
+        self.columns.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn error(&self) -> Option<String> {
+        self.error.clone()
+    }
 }
 
+/// Scores every column of the board from the input, for whoever's turn it
+/// is, as a win or loss in so many moves, a draw, or a heuristic weight.
 #[wasm_bindgen]
-pub fn process_data_for_player(data: &str) {
-    match serde_json::from_str::<Input>(data) {
-        Ok(inpt) => {
-            let game: Connect4 =
-                Connect4::init_from_input_for(Player::One, Player::AI, inpt.difficulty, inpt.board);
+pub fn analyze(data: &str) -> AnalysisResult {
+    match parse_input_for_side_to_move(data).and_then(|mut session| session.get_analysis()) {
+        Ok(analysis) => AnalysisResult::from_analysis(&analysis),
+        Err(err) => AnalysisResult::from_error(&err),
+    }
+}
+
+/// Solves the board from the input, giving the exact value of every column
+/// for whoever's turn it is, with both players playing perfectly. Result is
+/// the same as from `analyze`, without any heuristic weights.
+#[wasm_bindgen]
+pub fn solve(data: &str) -> AnalysisResult {
+    match parse_input_for_side_to_move(data).and_then(|session| session.get_solution()) {
+        Ok(analysis) => AnalysisResult::from_analysis(&analysis),
+        Err(err) => AnalysisResult::from_error(&err),
+    }
+}
+
+#[test]
+fn outcome_output_lists_winning_cells() {
+    let board = vec![vec![String::from("player"); 4], vec![String::from("ai"); 3]];
+    let game = Connect4::init_from_input_for(Player::One, Player::AI, Difficulty::Easy, board);
 
-            // Check if player won
-            connect4_player_win(game.is_current_player_winner());
+    assert_eq!(
+        serde_json::to_string(&outcome_to_output(&game)).unwrap(),
+        r#"{"status":"won","winner":"player","lines":[[{"col":0,"row":0},{"col":0,"row":1},{"col":0,"row":2},{"col":0,"row":3}]]}"#
+    );
+}
+
+#[test]
+fn input_rejects_unknown_difficulties() {
+    let board = r#"[["player"],[],[],[],[],[],[]]"#;
+    let parse = |difficulty: &str| {
+        parse_input(&format!(
+            r#"{{"difficulty":{},"player":"player","board":{}}}"#,
+            difficulty, board
+        ))
+    };
+
+    assert_eq!(
+        parse(r#""very_hard""#).unwrap().get_game().difficulty,
+        Difficulty::VeryHard
+    );
+    assert_eq!(
+        parse(r#"{"time_ms":100}"#).unwrap().get_game().difficulty,
+        Difficulty::TimeBudget(100.0)
+    );
+    assert_eq!(
+        parse(r#""impossible""#).unwrap_err(),
+        Connect4Error::UnknownDifficulty {
+            name: String::from("impossible")
         }
-        Err(err) => connect4_err(err.to_string().as_str()),
+    );
+}
+
+#[test]
+fn results_are_returned_instead_of_reported() {
+    let data = r#"{"difficulty":"easy","player":"player","board":[["player","player","player"],["ai","ai"],[],[],[],[],[]]}"#;
+    let result = ai_move(data);
+    assert_eq!(result.col(), Option::Some(1));
+    assert!(!result.won());
+    assert_eq!(result.error(), Option::None);
+
+    let data = r#"{"difficulty":"easy","player":"player","board":[["player","player","player","player"],["ai","ai","ai"],[],[],[],[],[]]}"#;
+    let result = player_move(data);
+    assert!(result.won());
+    assert!(result.outcome().unwrap().contains(r#""status":"won""#));
+
+    // Game is over, so the AI has nothing to play.
+    assert_eq!(ai_move(data).col(), Option::None);
+
+    let result = ai_move("{}");
+    assert_eq!(result.outcome(), Option::None);
+    assert!(result.error().unwrap().contains("invalid_json"));
+}
+
+#[test]
+fn analysis_is_for_the_side_to_move() {
+    // Player is to move, and wins with column 1.
+    let data = r#"{"difficulty":"easy","player":"player","board":[["player","player","player"],["ai","ai","ai"],[],[],[],[],[]]}"#;
+    let columns = analyze(data).columns().unwrap();
+    assert!(
+        columns.starts_with(r#"[{"col":1,"value":{"kind":"win","plies":1},"label":"win in 1"}"#)
+    );
+
+    let data = r#"{"difficulty":"easy","player":"player","board":[["player","player","player","player"],["ai","ai","ai"],[],[],[],[],[]]}"#;
+    let result = analyze(data);
+    assert_eq!(result.columns(), Option::None);
+    assert!(result.error().unwrap().contains("game_over"));
+}
+
+#[test]
+fn seed_from_the_input_decides_the_move() {
+    // Shallow search on an almost empty board, with plenty of ties to break.
+    let data = |seed: u64| {
+        format!(
+            r#"{{"difficulty":"test","player":"player","board":[[],[],[],["player"],[],[],[]],"seed":{}}}"#,
+            seed
+        )
     };
+
+    let played = ai_move(&data(3)).col();
+    (0..5).for_each(|_| assert_eq!(ai_move(&data(3)).col(), played));
+    assert_eq!(
+        parse_input(&data(3)).unwrap().get_game().seed,
+        Option::Some(3)
+    );
+}
+
+#[test]
+fn mcts_can_be_chosen_in_the_input() {
+    // Player one has three in the first column, which the AI has to block.
+    let data = |difficulty: &str| {
+        format!(
+            r#"{{"difficulty":{},"player":"player","board":[["player","player","player"],["ai","ai"],[],[],[],[],[]],"seed":5}}"#,
+            difficulty
+        )
+    };
+
+    assert_eq!(
+        ai_move(&data(r#"{"mcts":{"iterations":2000,"rollout":"random"}}"#)).col(),
+        Option::Some(1)
+    );
+    assert_eq!(ai_move(&data(r#""mcts""#)).col(), Option::Some(1));
+    assert!(parse_input(&data(r#"{"mcts":{"iterations":0}}"#)).is_err());
+}
+
+#[test]
+fn engine_can_be_chosen_in_the_input() {
+    // AI wins with the second column.
+    let data = |engine: &str| {
+        format!(
+            r#"{{"difficulty":"easy","engine":{},"player":"player","board":[["player","player","player"],["ai","ai","ai"],["player"],[],[],[],[]],"seed":2}}"#,
+            engine
+        )
+    };
+
+    assert_eq!(ai_move(&data(r#""greedy""#)).col(), Option::Some(2));
+    assert!(ai_move(&data(r#""random""#)).col().is_some());
+    assert!(ai_move(&data(r#""stockfish""#))
+        .error()
+        .unwrap()
+        .contains("unknown_engine"));
+}
+
+#[test]
+fn personality_can_be_chosen_in_the_input() {
+    // AI wins with the second column, and the player with the first.
+    let data = |personality: &str, seed: u64| {
+        format!(
+            r#"{{"difficulty":"normal","personality":{},"player":"player","board":[["player","player","player"],["ai","ai","ai"],["player"],[],[],[],[]],"seed":{}}}"#,
+            personality, seed
+        )
+    };
+
+    // Even the clumsiest personality takes a win in one.
+    let clumsy = r#"{"temperature":100,"blunder_rate":1}"#;
+    (0..10).for_each(|seed| assert_eq!(ai_move(&data(clumsy, seed)).col(), Option::Some(2)));
+
+    let careless = r#"{"blunder_rate":1,"takes_wins":false}"#;
+    assert_ne!(ai_move(&data(careless, 0)).col(), Option::Some(2));
+
+    assert!(ai_move(&data(r#""grandmaster""#, 0))
+        .error()
+        .unwrap()
+        .contains("unknown_personality"));
+}
+
+#[test]
+fn solving_gives_exact_values_for_the_side_to_move() {
+    let mut game = Connect4::init_vs_ai(Difficulty::Easy);
+    "2252576253462244111563365343671351441"
+        .chars()
+        .for_each(|col| {
+            game.drop_token_in_col(col.to_digit(10).unwrap() as u8)
+                .unwrap();
+            game.switch_players();
+        });
+    let data = format!(
+        r#"{{"difficulty":"easy","player":"player","board":{}}}"#,
+        serde_json::to_string(&game.get_board().get_input_board()).unwrap()
+    );
+
+    // AI is to move, and loses whatever it plays.
+    let columns = solve(&data).columns().unwrap();
+    assert!(columns.contains(r#""label":"loss in 4""#));
+    assert!(!columns.contains("heuristic") && !columns.contains("win"));
 }
@@ -0,0 +1,379 @@
+//! Monte Carlo tree search, with UCT (upper confidence bounds applied to
+//! trees) choosing which moves to look into. Instead of judging positions
+//! with a heuristic, it plays lots of games out to the end (rollouts), and
+//! grows a tree of the moves along the way, spending more of them on the
+//! moves winning more often.
+//!
+//! It plays in a different style than the minimax search. It doesn't see
+//! every short tactic, but favours moves which leave many ways to win, which
+//! feels more like playing against a person.
+
+use crate::clock::{Clock, Deadline};
+use crate::connect4::Connect4;
+use crate::control::CancelToken;
+use crate::enums::game_outcome::GameOutcome;
+use crate::enums::player::Player;
+use crate::evaluation::{column_mask, winning_cells};
+use crate::minimax::tie_breaker;
+use crate::models::board::{Bitboard, BOARD_MASK, BOTTOM_MASK, COL_HEIGHT, COL_NUM};
+#[cfg(test)]
+use crate::test_support::game_after;
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+/// Iterations when neither of the budgets is given.
+pub const DEFAULT_ITERATIONS: u32 = 10_000;
+
+/// How many iterations run between two looks at the clock.
+const ITERATIONS_PER_CLOCK_CHECK: u32 = 64;
+
+/// How the moves of a rollout are chosen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RolloutPolicy {
+    /// Any column, at random.
+    Random,
+    /// Winning move if there is one, otherwise a move blocking a win of the
+    /// opponent, otherwise a random column. Rollouts are slower, but play out
+    /// a lot more like real games.
+    Tactical,
+}
+
+/// Settings of the search, as they come in the JSON input, like
+/// `{"iterations": 5000, "exploration": 1.0, "rollout": "random"}`. Every
+/// field is optional. With both budgets, the search stops at whichever runs
+/// out first.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MctsConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iterations: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_ms: Option<f64>,
+    /// Weight of exploring moves tried less often, against playing the ones
+    /// winning the most. Square root of two in theory, lower in practice.
+    pub exploration: f64,
+    pub rollout: RolloutPolicy,
+}
+
+impl Default for MctsConfig {
+    fn default() -> MctsConfig {
+        MctsConfig {
+            iterations: Option::None,
+            time_ms: Option::None,
+            exploration: std::f64::consts::SQRT_2,
+            rollout: RolloutPolicy::Tactical,
+        }
+    }
+}
+
+impl MctsConfig {
+    pub fn is_valid(&self) -> bool {
+        self.iterations.is_none_or(|iterations| iterations > 0)
+            && self
+                .time_ms
+                .is_none_or(|time_ms| time_ms.is_finite() && time_ms > 0.0)
+            && self.exploration.is_finite()
+            && self.exploration >= 0.0
+    }
+
+    fn max_iterations(&self) -> u32 {
+        match (self.iterations, self.time_ms) {
+            (Option::Some(iterations), _) => iterations,
+            (Option::None, Option::Some(_)) => u32::MAX,
+            (Option::None, Option::None) => DEFAULT_ITERATIONS,
+        }
+    }
+}
+
+/// Column the search settled on, the most visited one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MctsResult {
+    pub col: Option<u8>,
+    /// Share of the rollouts through the column won by the player to move,
+    /// with draws counting half.
+    pub win_rate: f64,
+    pub iterations: u32,
+}
+
+/// Move leading to a position in the tree.
+#[derive(Debug, Clone)]
+struct Node {
+    col: u8, // Column played to get here, zero on the root
+    player: Player,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    untried: Vec<u8>,
+    visits: u32,
+    wins: f64, // For the player who played the column, draws count half
+}
+
+impl Node {
+    fn init(game: &Connect4, col: u8, player: Player, parent: Option<usize>) -> Node {
+        let untried = if game.get_outcome() == GameOutcome::InProgress {
+            (1..=COL_NUM as u8)
+                .filter(|col| !game.is_column_full(*col))
+                .collect()
+        } else {
+            vec![]
+        };
+
+        Node {
+            col,
+            player,
+            parent,
+            children: vec![],
+            untried,
+            visits: 0,
+            wins: 0.0,
+        }
+    }
+
+    /// UCT value of the node, seen from its parent with so many visits.
+    fn uct(&self, parent_visits: u32, exploration: f64) -> f64 {
+        let visits = self.visits as f64;
+        self.wins / visits + exploration * ((parent_visits as f64).ln() / visits).sqrt()
+    }
+}
+
+/// Searches the position for the player to move, until one of the budgets of
+/// the config runs out, or the search is cancelled.
+pub fn search(
+    game: &Connect4,
+    config: &MctsConfig,
+    clock: &dyn Clock,
+    cancel: Option<&CancelToken>,
+) -> MctsResult {
+    let deadline = config
+        .time_ms
+        .map(|budget_ms| Deadline::init(clock, budget_ms));
+    let mut rng = tie_breaker(game);
+    // Root isn't reached by a move, so its player doesn't matter.
+    let mut tree = vec![Node::init(game, 0, game.get_current_player(), Option::None)];
+    let mut iterations = 0;
+
+    while iterations < config.max_iterations() {
+        if iterations % ITERATIONS_PER_CLOCK_CHECK == 0
+            && iterations > 0
+            && (deadline.as_ref().is_some_and(|d| d.has_passed())
+                || cancel.is_some_and(|c| c.is_cancelled()))
+        {
+            break;
+        }
+        iterate(game, &mut tree, config, &mut rng);
+        iterations += 1;
+    }
+
+    let best = tree[0]
+        .children
+        .iter()
+        .map(|idx| &tree[*idx])
+        .max_by(|a, b| (a.visits, a.wins).partial_cmp(&(b.visits, b.wins)).unwrap());
+
+    MctsResult {
+        col: best.map(|node| node.col),
+        win_rate: best.map_or(0.5, |node| node.wins / node.visits as f64),
+        iterations,
+    }
+}
+
+/// Selects a leaf of the tree with UCT, expands it with one of the moves it
+/// hasn't tried yet, plays the game out from there, and counts the result on
+/// every node on the way back up.
+fn iterate(root: &Connect4, tree: &mut Vec<Node>, config: &MctsConfig, rng: &mut dyn RngCore) {
+    let mut game = root.clone();
+    let mut idx = 0;
+
+    while tree[idx].untried.is_empty() && !tree[idx].children.is_empty() {
+        let parent_visits = tree[idx].visits;
+        idx = *tree[idx]
+            .children
+            .iter()
+            .max_by(|a, b| {
+                let a = tree[**a].uct(parent_visits, config.exploration);
+                let b = tree[**b].uct(parent_visits, config.exploration);
+                a.partial_cmp(&b).unwrap()
+            })
+            .unwrap();
+        play(&mut game, tree[idx].col);
+    }
+
+    if !tree[idx].untried.is_empty() {
+        let col = {
+            let untried = &mut tree[idx].untried;
+            untried.swap_remove(rng.gen_range(0..untried.len()))
+        };
+        let player = game.get_current_player();
+        play(&mut game, col);
+        tree.push(Node::init(&game, col, player, Option::Some(idx)));
+        let child = tree.len() - 1;
+        tree[idx].children.push(child);
+        idx = child;
+    }
+
+    let winner = rollout(&mut game, config.rollout, rng);
+
+    let mut node = Option::Some(idx);
+    while let Option::Some(idx) = node {
+        tree[idx].visits += 1;
+        tree[idx].wins += match winner {
+            Option::Some(player) if player == tree[idx].player => 1.0,
+            Option::Some(_) => 0.0,
+            Option::None => 0.5,
+        };
+        node = tree[idx].parent;
+    }
+}
+
+/// Plays the game out to the end, returning the winner, if there is one.
+fn rollout(game: &mut Connect4, policy: RolloutPolicy, rng: &mut dyn RngCore) -> Option<Player> {
+    loop {
+        match game.get_outcome() {
+            GameOutcome::Won(player, _) => return Option::Some(player),
+            GameOutcome::Draw => return Option::None,
+            GameOutcome::InProgress => {
+                let col = rollout_col(game, policy, rng);
+                play(game, col);
+            }
+        }
+    }
+}
+
+fn rollout_col(game: &Connect4, policy: RolloutPolicy, rng: &mut dyn RngCore) -> u8 {
+    let board = game.get_board();
+    let played = board.get_played_mask();
+    let playable = (played + BOTTOM_MASK) & BOARD_MASK;
+
+    if policy == RolloutPolicy::Tactical {
+        let own = board.get_tokens_for_player(&game.get_current_player());
+        let wins = winning_cells(own, played) & playable;
+        let blocks = winning_cells(played ^ own, played) & playable;
+
+        if let Option::Some(cell) = [wins, blocks].iter().find(|cells| **cells != 0) {
+            return cell_to_col(*cell);
+        }
+    }
+
+    let mut cols = [0; COL_NUM as usize];
+    let mut len = 0;
+    (0..COL_NUM as usize).for_each(|col| {
+        if playable & column_mask(col) != 0 {
+            cols[len] = col as u8 + 1;
+            len += 1;
+        }
+    });
+    cols[rng.gen_range(0..len)]
+}
+
+/// Column of the lowest set cell, counted from one.
+fn cell_to_col(cells: Bitboard) -> u8 {
+    (cells.trailing_zeros() / COL_HEIGHT as u32) as u8 + 1
+}
+
+fn play(game: &mut Connect4, col: u8) {
+    let played = game.drop_token_in_col(col);
+    debug_assert!(played.is_ok());
+    game.switch_players();
+}
+
+// Module tests!!
+
+#[cfg(test)]
+fn seeded_game(moves: &str) -> Connect4 {
+    use crate::enums::difficulty::Difficulty;
+
+    let mut game = game_after(Difficulty::Mcts(MctsConfig::default()), moves);
+    game.seed = Option::Some(7);
+    game
+}
+
+#[cfg(test)]
+fn config(iterations: u32, rollout: RolloutPolicy) -> MctsConfig {
+    MctsConfig {
+        iterations: Option::Some(iterations),
+        rollout,
+        ..MctsConfig::default()
+    }
+}
+
+#[test]
+fn plays_winning_moves_and_blocks() {
+    use crate::clock::SystemClock;
+
+    [RolloutPolicy::Random, RolloutPolicy::Tactical]
+        .iter()
+        .for_each(|rollout| {
+            let config = config(2000, *rollout);
+
+            // Player one has three in column 1, and wins there.
+            let result = search(&seeded_game("121217"), &config, &SystemClock, Option::None);
+            assert_eq!(result.col, Option::Some(1), "{:?}", rollout);
+            assert!(result.win_rate > 0.9);
+
+            // AI has to block column 1.
+            let result = search(&seeded_game("12121"), &config, &SystemClock, Option::None);
+            assert_eq!(result.col, Option::Some(1), "{:?}", rollout);
+        });
+}
+
+#[test]
+fn same_seed_plays_the_same_move() {
+    use crate::clock::SystemClock;
+
+    let game = seeded_game("4453");
+    let play = || {
+        search(
+            &game,
+            &config(500, RolloutPolicy::Random),
+            &SystemClock,
+            Option::None,
+        )
+    };
+    assert_eq!(play(), play());
+    assert_eq!(play().iterations, 500);
+}
+
+#[test]
+fn stops_once_out_of_time_or_cancelled() {
+    use crate::clock::FakeClock;
+
+    let game = seeded_game("");
+    let config = MctsConfig {
+        time_ms: Option::Some(10.0),
+        ..MctsConfig::default()
+    };
+    // Clock moves on a millisecond every time it's read, once per check.
+    let result = search(&game, &config, &FakeClock::init(1.0), Option::None);
+    assert!(result.col.is_some());
+    assert!(result.iterations <= 10 * ITERATIONS_PER_CLOCK_CHECK);
+
+    let cancel = CancelToken::init();
+    cancel.cancel();
+    let result = search(
+        &game,
+        &MctsConfig::default(),
+        &FakeClock::init(1.0),
+        Option::Some(&cancel),
+    );
+    assert_eq!(result.iterations, ITERATIONS_PER_CLOCK_CHECK);
+    assert!(result.col.is_some());
+}
+
+#[test]
+fn config_is_read_from_json() {
+    let parse = |json: &str| serde_json::from_str::<MctsConfig>(json).unwrap();
+
+    assert_eq!(parse("{}"), MctsConfig::default());
+    assert_eq!(
+        parse(r#"{"iterations":500,"exploration":0.7,"rollout":"random"}"#),
+        MctsConfig {
+            iterations: Option::Some(500),
+            time_ms: Option::None,
+            exploration: 0.7,
+            rollout: RolloutPolicy::Random,
+        }
+    );
+    assert!(!parse(r#"{"iterations":0}"#).is_valid());
+    assert!(!parse(r#"{"exploration":-1}"#).is_valid());
+    assert!(parse(r#"{"time_ms":200}"#).is_valid());
+}
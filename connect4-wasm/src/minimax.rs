@@ -1,9 +1,25 @@
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
 
 /// App mods
+use crate::book::{lookup, BookMove};
+use crate::clock::{Clock, Deadline, SystemClock};
 use crate::connect4::Connect4;
+use crate::control::{CancelToken, Progress};
 use crate::enums::difficulty::*;
-use crate::models::board::{is_successful_move, COL_NUM};
+use crate::enums::game_outcome::GameOutcome;
+use crate::evaluation::{Weight, WIN_WEIGHT};
+use crate::mcts::{self, MctsConfig};
+use crate::models::board::{mirror_col, COL_NUM};
+use crate::ordering::MoveOrdering;
+use crate::score::Score;
+use crate::solver::Solver;
+#[cfg(test)]
+use crate::test_support::{drawn_game, game_after};
+use crate::transposition::{Bound, Entry, TranspositionTable};
+
+/// How many nodes are searched between two looks at the clock.
+const NODES_PER_CLOCK_CHECK: u64 = 256;
 
 /// Type indicating minimax type
 #[derive(Debug, PartialEq)]
@@ -20,138 +36,1162 @@ fn switch_procedure(current: &Procedure) -> Procedure {
     }
 }
 
-/// Entry point for the minimax algorithm.
-pub fn play_for_ai(game: &Connect4) -> u8 {
-    // We're assuming that we want to maximise for the current player!
-    minimax_run(
+/// State shared by all the nodes of a single search.
+struct Search<'a> {
+    table: &'a mut TranspositionTable,
+    deadline: Option<&'a Deadline<'a>>,
+    cancel: Option<&'a CancelToken>,
+    ordering: MoveOrdering,
+    nodes: u64,
+    aborted: bool,
+}
+
+impl<'a> Search<'a> {
+    fn init(table: &'a mut TranspositionTable, deadline: Option<&'a Deadline<'a>>) -> Search<'a> {
+        Search {
+            table,
+            deadline,
+            cancel: Option::None,
+            ordering: MoveOrdering::init(),
+            nodes: 0,
+            aborted: false,
+        }
+    }
+
+    /// Search also stops once the token is cancelled.
+    fn with_cancel(mut self, cancel: Option<&'a CancelToken>) -> Search<'a> {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Columns are searched from left to right, for comparing node counts.
+    fn without_move_ordering(mut self) -> Search<'a> {
+        self.ordering = MoveOrdering::init_disabled();
+        self
+    }
+
+    /// Counts the node, and tells if the search ran out of time, or was
+    /// cancelled. The clock and the token are checked on the very first node,
+    /// and then every so often. Once the search is aborted it stays aborted,
+    /// so it unwinds quickly.
+    fn should_abort(&mut self) -> bool {
+        self.nodes += 1;
+
+        if !self.aborted && self.nodes % NODES_PER_CLOCK_CHECK == 1 {
+            self.aborted = self.deadline.is_some_and(|d| d.has_passed())
+                || self.cancel.is_some_and(|c| c.is_cancelled());
+        }
+        self.aborted
+    }
+}
+
+/// Entry point for the minimax algorithm. There's no column to play only if
+/// the board is already full. Difficulties with a time budget are searched
+/// for as long as the budget allows.
+pub fn play_for_ai(game: &Connect4) -> Option<u8> {
+    play_for_ai_with_table(game, &mut TranspositionTable::init_default())
+}
+
+/// Same as `play_for_ai`, but with a transposition table owned by the caller,
+/// so it can be configured, or kept around between moves.
+pub fn play_for_ai_with_table(game: &Connect4, table: &mut TranspositionTable) -> Option<u8> {
+    let depth = difficulty_to_depth(game.difficulty);
+
+    match difficulty_to_time_budget(game.difficulty) {
+        Option::Some(budget_ms) => {
+            iterative_deepening(game, depth, budget_ms, &SystemClock, table).0
+        }
+        Option::None => {
+            let mut search = Search::init(table, Option::None);
+            select_column(game, search_columns(game, depth, &mut search))
+        }
+    }
+}
+
+/// Searches deeper and deeper, up to the depth set by the difficulty, until
+/// the time budget runs out. Returns the best column found by the deepest
+/// search that was able to finish. The first depth is always searched in full,
+/// so there's always a move to play.
+pub fn play_for_ai_within(game: &Connect4, budget_ms: f64, clock: &dyn Clock) -> Option<u8> {
+    let max_depth = difficulty_to_depth(game.difficulty);
+    let mut table = TranspositionTable::init_default();
+    iterative_deepening(game, max_depth, budget_ms, clock, &mut table).0
+}
+
+/// Same as `play_for_ai_with_table`, but the search can be cancelled with the
+/// token, and reports its progress after every depth. It's always searched
+/// with iterative deepening, so there's progress to report, and a move to
+/// play if it's cancelled. Time budget of the difficulty is read from the
+/// clock.
+pub fn play_for_ai_controlled(
+    game: &Connect4,
+    table: &mut TranspositionTable,
+    clock: &dyn Clock,
+    cancel: Option<&CancelToken>,
+    on_progress: &mut dyn FnMut(&Progress),
+) -> Option<u8> {
+    let max_depth = difficulty_to_depth(game.difficulty);
+    let deadline = difficulty_to_time_budget(game.difficulty)
+        .map(|budget_ms| Deadline::init(clock, budget_ms));
+
+    run_deepening(
         game,
-        Procedure::Maximising,
-        difficulty_to_depth(game.difficulty.clone()),
+        max_depth,
+        table,
+        deadline.as_ref(),
+        cancel,
+        on_progress,
     )
     .0
 }
 
-fn minimax_run(game: &Connect4, procedure: Procedure, depth: u8) -> (u8, i8) {
-    let default_res = (1, 0);
+/// Returns the chosen column along with the depth it was chosen at.
+fn iterative_deepening(
+    game: &Connect4,
+    max_depth: u8,
+    budget_ms: f64,
+    clock: &dyn Clock,
+    table: &mut TranspositionTable,
+) -> (Option<u8>, u8) {
+    let deadline = Deadline::init(clock, budget_ms);
+    run_deepening(
+        game,
+        max_depth,
+        table,
+        Option::Some(&deadline),
+        Option::None,
+        &mut |_| {},
+    )
+}
 
-    let column_weights: Vec<(u8, i8)> = (1..(COL_NUM + 1) as u8)
-        .filter_map(|col_idx| {
-            let mut game_copy = game.clone();
-            let success = game_copy.drop_token_in_col(col_idx);
-
-            if is_successful_move(&success) {
-                Option::Some(on_successful_token_drop(
-                    &mut game_copy,
-                    &procedure,
-                    &depth,
-                    &col_idx,
-                ))
+fn run_deepening(
+    game: &Connect4,
+    max_depth: u8,
+    table: &mut TranspositionTable,
+    deadline: Option<&Deadline>,
+    cancel: Option<&CancelToken>,
+    on_progress: &mut dyn FnMut(&Progress),
+) -> (Option<u8>, u8) {
+    let mut deepening = Deepening::init(game, max_depth);
+
+    while !deepening.is_done() {
+        // First depth can't be stopped, so there's always a move to play.
+        let completed = if deepening.get_depth().is_none() {
+            deepening.step(game, table, Option::None, Option::None)
+        } else {
+            deepening.step(game, table, deadline, cancel)
+        };
+        if !completed {
+            break;
+        }
+        if let Option::Some(progress) = deepening.get_progress() {
+            on_progress(&progress);
+        }
+    }
+
+    (deepening.get_best_col(), deepening.get_depth().unwrap_or(0))
+}
+
+/// Iterative deepening, searched one depth at a time, so the caller can do
+/// other things in between, like reporting progress or stopping early.
+#[derive(Debug, Clone)]
+pub struct Deepening {
+    next_depth: u8,
+    max_depth: u8,
+    chosen: Option<(Option<u8>, u8)>, // Best column, and the depth it was found at
+    nodes: u64,
+}
+
+impl Deepening {
+    pub fn init(game: &Connect4, max_depth: u8) -> Deepening {
+        // No point looking further than the number of moves left in the game.
+        let max_depth = max_depth.min(game.get_board().count_moves_left().saturating_sub(1));
+
+        Deepening {
+            // Solver, MCTS and the book go all the way in a single step.
+            next_depth: if matches!(game.difficulty, Difficulty::Perfect | Difficulty::Mcts(_))
+                || book_move(game).is_some()
+            {
+                max_depth
             } else {
-                Option::None
-            }
+                0
+            },
+            max_depth,
+            chosen: Option::None,
+            nodes: 0,
+        }
+    }
+
+    /// Searches the next depth. Returns false, keeping the result of the last
+    /// depth, if the deadline passed, or the search was cancelled before it
+    /// could finish.
+    pub fn step(
+        &mut self,
+        game: &Connect4,
+        table: &mut TranspositionTable,
+        deadline: Option<&Deadline>,
+        cancel: Option<&CancelToken>,
+    ) -> bool {
+        let depth = self.next_depth;
+        let mut search = Search::init(table, deadline).with_cancel(cancel);
+        let column_scores = search_columns(game, depth, &mut search);
+        self.nodes += search.nodes;
+
+        if search.aborted {
+            self.next_depth = self.max_depth.saturating_add(1);
+            return false;
+        }
+
+        self.chosen = Option::Some((select_column(game, column_scores), depth));
+        self.next_depth = depth.saturating_add(1);
+        true
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.next_depth > self.max_depth
+    }
+
+    /// Deepest depth searched in full so far.
+    pub fn get_depth(&self) -> Option<u8> {
+        self.chosen.map(|(_, depth)| depth)
+    }
+
+    pub fn get_best_col(&self) -> Option<u8> {
+        self.chosen.and_then(|(col, _)| col)
+    }
+
+    /// Progress after the deepest depth searched in full, if there's one.
+    pub fn get_progress(&self) -> Option<Progress> {
+        self.chosen.map(|(best_col, depth)| Progress {
+            depth,
+            nodes: self.nodes,
+            best_col,
+        })
+    }
+}
+
+/// Scores of all playable columns for the current player, searched as deep as
+/// the depth. On the perfect difficulty the game is solved instead, so the
+/// scores are exact, but the search can't be stopped early. Positions in the
+/// opening book only get the column of the book.
+fn search_columns(game: &Connect4, depth: u8, search: &mut Search) -> Vec<(u8, Score)> {
+    if let Option::Some(book_move) = book_move(game) {
+        return vec![(book_move.col, book_move.score)];
+    }
+    if let Difficulty::Mcts(config) = game.difficulty {
+        return mcts_column(game, &config, search);
+    }
+    if game.difficulty != Difficulty::Perfect {
+        return root_column_scores(game, depth, search);
+    }
+
+    let mut solver = Solver::init();
+    let scores = solver.get_column_scores(game);
+    search.nodes += solver.get_nodes();
+    scores
+}
+
+/// Column chosen by MCTS, scored by how often it won, from -999 when it
+/// never did to 999 when it always did.
+fn mcts_column(game: &Connect4, config: &MctsConfig, search: &mut Search) -> Vec<(u8, Score)> {
+    let result = mcts::search(game, config, &SystemClock, search.cancel);
+    search.nodes += result.iterations as u64;
+
+    let weight = ((result.win_rate - 0.5) * 2.0 * (WIN_WEIGHT - 1) as f64).round() as Weight;
+    result
+        .col
+        .map(|col| vec![(col, Score::from_heuristic(weight))])
+        .unwrap_or_default()
+}
+
+/// Move of the opening book, if the difficulty plays by the book, and the
+/// position is in it. Personalities straying from the best column don't play
+/// by the book, which only has that one.
+fn book_move(game: &Connect4) -> Option<BookMove> {
+    if difficulty_uses_book(game.difficulty) && !game.personality.is_some_and(|p| p.strays()) {
+        lookup(game)
+    } else {
+        Option::None
+    }
+}
+
+/// Scores of all playable columns for the current player. Only the columns
+/// sharing the best score are guaranteed to hold their exact minimax value,
+/// the rest are upper bounds, which is enough to tell them apart.
+fn root_column_scores(game: &Connect4, depth: u8, search: &mut Search) -> Vec<(u8, Score)> {
+    let exact_scores = game.personality.is_some_and(|p| p.needs_exact_scores());
+    let mut game = game.clone();
+    let mut best_score = Score::MIN;
+    let (columns, len) = search.ordering.order_columns(&game, 0, Option::None);
+
+    let mut scores: Vec<(u8, Score)> = columns[..len]
+        .iter()
+        .filter_map(|&col_idx| {
+            with_token_in_col(&mut game, col_idx, |game| {
+                // Lowering alpha by one keeps moves that tie with the best one
+                // exact, so we can still pick randomly between them. With a
+                // personality playing worse moves too, every move is exact.
+                let alpha = if exact_scores {
+                    Score::MIN
+                } else {
+                    best_score.just_below()
+                };
+                let score = on_successful_token_drop(
+                    game,
+                    &Procedure::Maximising,
+                    depth,
+                    1,
+                    alpha,
+                    Score::MAX,
+                    search,
+                );
+                best_score = best_score.max(score);
+                (col_idx, score)
+            })
         })
         .collect();
+    scores.sort_by_key(|(col_idx, _)| *col_idx);
+    scores
+}
 
-    let only_weights: Vec<i8> = column_weights.clone().into_iter().map(|w| w.1).collect();
+/// Nodes searched choosing a column at the depth, starting with an empty
+/// table, with or without move ordering, to see how much the ordering prunes.
+pub fn count_nodes(game: &Connect4, depth: u8, ordered: bool) -> u64 {
+    let mut table = TranspositionTable::init_default();
+    let mut search = Search::init(&mut table, Option::None);
+    if !ordered {
+        search = search.without_move_ordering();
+    }
+    root_column_scores(game, depth, &mut search);
+    search.nodes
+}
 
-    let target_weight: Option<i8> = match procedure {
-        Procedure::Maximising => only_weights.into_iter().max(),
-        Procedure::Minimising => only_weights.into_iter().min(),
-    };
+/// Exact scores of all playable columns for the current player. Unlike
+/// `root_column_scores`, every column is searched with the full window, so
+/// it's slower, but the scores can be compared with each other.
+pub fn analyze_columns(
+    game: &Connect4,
+    depth: u8,
+    table: &mut TranspositionTable,
+) -> Vec<(u8, Score)> {
+    let mut search = Search::init(table, Option::None);
+    let mut game = game.clone();
+
+    (1..(COL_NUM + 1) as u8)
+        .filter_map(|col_idx| {
+            with_token_in_col(&mut game, col_idx, |game| {
+                let score = on_successful_token_drop(
+                    game,
+                    &Procedure::Maximising,
+                    depth,
+                    1,
+                    Score::MIN,
+                    Score::MAX,
+                    &mut search,
+                );
+                (col_idx, score)
+            })
+        })
+        .collect()
+}
+
+/// Drops a token in the column, runs `f` with it on the board, and takes it
+/// back, so the search plays all its moves on a single game instead of
+/// copying it for every column. Returns None, without running `f`, if the
+/// column can't be played.
+fn with_token_in_col<T>(
+    game: &mut Connect4,
+    col_idx: u8,
+    f: impl FnOnce(&mut Connect4) -> T,
+) -> Option<T> {
+    game.drop_token_in_col(col_idx).ok()?;
+    let result = f(game);
+    let undone = game.undo_drop_in_col(col_idx);
+    debug_assert!(undone.is_some());
+    Option::Some(result)
+}
+
+/// Random generator for breaking ties between the best columns. When the game
+/// has a seed, the generator is seeded with it and the position, so the same
+/// position always gets the same move.
+pub fn tie_breaker(game: &Connect4) -> StdRng {
+    match game.seed {
+        Option::Some(seed) => StdRng::seed_from_u64(seed ^ game.get_key()),
+        Option::None => StdRng::from_entropy(),
+    }
+}
+
+/// Column the AI plays, out of the scored ones, picked by its personality if
+/// it has one, and the best one otherwise.
+pub fn select_column(game: &Connect4, column_scores: Vec<(u8, Score)>) -> Option<u8> {
+    match game.personality {
+        Option::Some(personality) => personality.choose_col(&column_scores, &mut tie_breaker(game)),
+        Option::None => select_best_column(column_scores, &mut tie_breaker(game)),
+    }
+}
+
+/// Pick one of the columns with the highest score, randomly if there's more
+/// than one of them.
+fn select_best_column(column_scores: Vec<(u8, Score)>, rng: &mut dyn RngCore) -> Option<u8> {
+    let score = column_scores.iter().map(|w| w.1).max()?;
+    let possible_moves: Vec<u8> = column_scores
+        .into_iter()
+        .filter(|tpl| tpl.1 == score)
+        .map(|tpl| tpl.0)
+        .collect();
+
+    match possible_moves.as_slice() {
+        [col] => Option::Some(*col),
+        _ => {
+            let selected_idx = rng.gen_range(0..possible_moves.len());
+            possible_moves.get(selected_idx).copied()
+        }
+    }
+}
+
+/// Alpha-beta minimax run, returns the best column and its score. Alpha is
+/// the score the maximising player is already assured of, and beta the one
+/// the minimising player is, so once they cross the rest of the columns can't
+/// change the outcome and are skipped. Ply is the number of moves played
+/// since the root of the search.
+fn minimax_run(
+    game: &mut Connect4,
+    procedure: Procedure,
+    depth: u8,
+    ply: u8,
+    mut alpha: Score,
+    mut beta: Score,
+    search: &mut Search,
+) -> (u8, Score) {
+    let default_res = (1, Score::DRAW);
+
+    if search.should_abort() {
+        return default_res;
+    }
+
+    // Mirrored positions share their entry, which keeps the best column as
+    // it's played in the canonical position.
+    let (key, mirrored) = game.get_canonical_key();
+    let (alpha_orig, beta_orig) = (alpha, beta);
 
-    match target_weight {
-        Option::Some(weight) => {
-            let possible_moves: Vec<(u8, i8)> = column_weights
-                .into_iter()
-                .filter(|tpl| tpl.1 == weight)
-                .collect();
-            match possible_moves.as_slice() {
-                [] => default_res,
-                [res] => res.clone(),
-                _ => {
-                    let selected_idx = rand::thread_rng().gen_range(0..possible_moves.len());
-                    match possible_moves.get(selected_idx) {
-                        Option::Some(&val) => val,
-                        Option::None => default_res,
-                    }
+    // Same position, or its mirror image, was already searched at least as
+    // deep through a different order of moves. Its score is only as good as
+    // its bound, so bounds narrow the window instead of ending the search.
+    let entry = search.table.get(key);
+    if let Option::Some(entry) = entry.filter(|e| e.depth >= depth) {
+        let (score, bound) = from_side_to_move(entry.score.to_root(ply), entry.bound, &procedure);
+        let best_col = orient_col(entry.best_col, mirrored);
+        match bound {
+            Bound::Exact => return (best_col, score),
+            Bound::Lower => alpha = alpha.max(score),
+            Bound::Upper => beta = beta.min(score),
+        }
+        if alpha >= beta {
+            return (best_col, score);
+        }
+    }
+
+    // Best column of a search to a different depth is still a good guess.
+    let hint = entry.map(|e| orient_col(e.best_col, mirrored));
+    let (columns, len) = search.ordering.order_columns(game, ply, hint);
+    let mut best: Option<(u8, Score)> = Option::None;
+
+    for &col_idx in &columns[..len] {
+        let score = match with_token_in_col(game, col_idx, |game| {
+            on_successful_token_drop(game, &procedure, depth, ply + 1, alpha, beta, search)
+        }) {
+            Option::Some(score) => score,
+            Option::None => continue,
+        };
+
+        match procedure {
+            Procedure::Maximising => {
+                if best.is_none_or(|(_, w)| score > w) {
+                    best = Option::Some((col_idx, score));
+                }
+                alpha = alpha.max(score);
+            }
+            Procedure::Minimising => {
+                if best.is_none_or(|(_, w)| score < w) {
+                    best = Option::Some((col_idx, score));
                 }
+                beta = beta.min(score);
             }
         }
-        Option::None => default_res,
+
+        if search.aborted {
+            break;
+        }
+        if alpha >= beta {
+            search.ordering.on_cutoff(game, col_idx, ply, depth);
+            break;
+        }
+    }
+
+    // Scores of an aborted search can't be trusted, so keep them out of the
+    // table.
+    if search.aborted {
+        return default_res;
+    }
+
+    let (best_col, score) = best.unwrap_or(default_res);
+    let bound = if score <= alpha_orig {
+        Bound::Upper
+    } else if score >= beta_orig {
+        Bound::Lower
+    } else {
+        Bound::Exact
+    };
+    let (stored_score, stored_bound) = from_side_to_move(score, bound, &procedure);
+    search.table.store(Entry {
+        key,
+        depth,
+        score: stored_score.to_table(ply),
+        bound: stored_bound,
+        best_col: orient_col(best_col, mirrored),
+    });
+
+    (best_col, score)
+}
+
+/// Mirrors the column if the position is mirrored.
+fn orient_col(col: u8, mirrored: bool) -> u8 {
+    if mirrored {
+        mirror_col(col)
+    } else {
+        col
     }
 }
 
+/// Table keeps scores from the point of view of the player to move, while
+/// the search keeps them from the point of view of the maximising player, so
+/// for the minimising player the score is negated and the bound flipped. The
+/// conversion is the same both ways.
+fn from_side_to_move(score: Score, bound: Bound, procedure: &Procedure) -> (Score, Bound) {
+    match procedure {
+        Procedure::Maximising => (score, bound),
+        Procedure::Minimising => (
+            -score,
+            match bound {
+                Bound::Exact => Bound::Exact,
+                Bound::Lower => Bound::Upper,
+                Bound::Upper => Bound::Lower,
+            },
+        ),
+    }
+}
+
+/// Scores the move that was just played, which was the move on `ply`.
 fn on_successful_token_drop(
     game: &mut Connect4,
     procedure: &Procedure,
-    depth: &u8,
-    col: &u8,
-) -> (u8, i8) {
-    let is_column_full = game.is_column_full(*col);
-    let is_game_finished = game.is_current_player_winner();
-
-    (
-        *col,
-        // If game's finished, or we can't drop any more tokens in column, or
-        // we've reached the max depth for the algorithm.
-        if is_column_full || is_game_finished || *depth == 0 {
-            get_current_move_weight(&game, &procedure, &depth)
-        // We can add more tokens in this column, and we're not at full depth
-        } else {
+    depth: u8,
+    ply: u8,
+    alpha: Score,
+    beta: Score,
+    search: &mut Search,
+) -> Score {
+    match game.get_outcome() {
+        GameOutcome::InProgress if depth > 0 => {
             game.switch_players();
 
-            // Just taking the chosen weight from the next minimax run, as this
-            // will become the weight of the current column!
-            minimax_run(&game, switch_procedure(&procedure), *depth - 1).1
-        },
-    )
+            // Just taking the chosen score from the next minimax run, as this
+            // will become the score of the current column!
+            let score = minimax_run(
+                game,
+                switch_procedure(procedure),
+                depth - 1,
+                ply,
+                alpha,
+                beta,
+                search,
+            )
+            .1;
+            game.switch_players();
+            score
+        }
+        // If game's finished or we've reached the max depth for the algorithm.
+        outcome => get_current_move_score(game, &outcome, procedure, ply),
+    }
 }
 
 /// Taking into account current state on the board, return value depending on
-/// the maximising or minimising step!
-fn get_current_move_weight(game: &Connect4, procedure: &Procedure, depth: &u8) -> i8 {
-    let win = game.is_current_player_winner();
-    if win {
-        (*depth as i8 + 1)
-            * match procedure {
-                Procedure::Maximising => 1,
-                Procedure::Minimising => -1,
-            }
-    } else {
-        0
+/// the maximising or minimising step! Wins are scored by how many plies away
+/// from the root they are, so sooner wins score more, draws are neutral, and
+/// if the game is still going the position is judged by the heuristic
+/// evaluation.
+fn get_current_move_score(
+    game: &Connect4,
+    outcome: &GameOutcome,
+    procedure: &Procedure,
+    ply: u8,
+) -> Score {
+    let score = match outcome {
+        GameOutcome::Won(player, _) if *player == game.get_current_player() => Score::win_in(ply),
+        GameOutcome::Won(_, _) => Score::loss_in(ply),
+        GameOutcome::Draw => Score::DRAW,
+        GameOutcome::InProgress => Score::from_heuristic(game.evaluate_for_current_player()),
+    };
+
+    match procedure {
+        Procedure::Maximising => score,
+        Procedure::Minimising => -score,
     }
 }
 
+/// Plain minimax without any pruning, used as a reference for the alpha-beta
+/// search in tests.
+#[cfg(test)]
+fn minimax_reference(game: &Connect4, procedure: &Procedure, depth: u8, ply: u8) -> Option<Score> {
+    let scores = (1..(COL_NUM + 1) as u8).filter_map(|col_idx| {
+        let mut game_copy = game.clone();
+        if game_copy.drop_token_in_col(col_idx).is_err() {
+            return Option::None;
+        }
+        Option::Some(reference_token_drop(
+            &mut game_copy,
+            procedure,
+            depth,
+            ply + 1,
+        ))
+    });
+
+    match procedure {
+        Procedure::Maximising => scores.max(),
+        Procedure::Minimising => scores.min(),
+    }
+}
+
+#[cfg(test)]
+fn reference_token_drop(game: &mut Connect4, procedure: &Procedure, depth: u8, ply: u8) -> Score {
+    match game.get_outcome() {
+        GameOutcome::InProgress if depth > 0 => {
+            game.switch_players();
+            minimax_reference(game, &switch_procedure(procedure), depth - 1, ply).unwrap()
+        }
+        outcome => get_current_move_score(game, &outcome, procedure, ply),
+    }
+}
+
+/// Positions played out from an empty board, with players taking turns.
+#[cfg(test)]
+fn test_positions() -> Vec<Connect4> {
+    let sequences: Vec<Vec<u8>> = vec![
+        vec![],
+        vec![4],
+        vec![4, 4],
+        vec![4, 3, 4, 3],
+        vec![1, 2, 3, 4, 5, 6, 7],
+        vec![4, 4, 4, 4, 3, 5],
+        vec![3, 4, 3, 4, 2, 5, 6],
+        vec![1, 1, 2, 2, 6, 6, 7],
+        vec![4, 5, 4, 5, 4, 3, 3, 2],
+        vec![7, 7, 7, 7, 7, 7, 1, 1, 1],
+    ];
+
+    sequences
+        .iter()
+        .map(|moves| {
+            let mut game = Connect4::init_vs_ai(Difficulty::Normal);
+            moves.iter().for_each(|col| {
+                game.drop_token_in_col(*col).unwrap();
+                game.switch_players();
+            });
+            game
+        })
+        .collect()
+}
+
 #[test]
 fn ai_should_play_col_3() {
     let mut game = Connect4::init_vs_ai(Difficulty::Normal);
 
-    game.drop_token_in_col(3);
-    game.drop_token_in_col(3);
-    game.drop_token_in_col(3);
+    game.drop_token_in_col(3).unwrap();
+    game.drop_token_in_col(3).unwrap();
+    game.drop_token_in_col(3).unwrap();
 
-    assert_eq!(play_for_ai(&game), 3);
+    assert_eq!(play_for_ai(&game), Option::Some(3));
 }
 
 #[test]
 fn ai_should_play_col_1_or_5() {
     let mut game = Connect4::init_vs_ai(Difficulty::Normal);
 
-    game.drop_token_in_col(2);
-    game.drop_token_in_col(3);
-    game.drop_token_in_col(4);
+    game.drop_token_in_col(2).unwrap();
+    game.drop_token_in_col(3).unwrap();
+    game.drop_token_in_col(4).unwrap();
 
-    let played = play_for_ai(&game);
-    assert_eq!(played == 1 || played == 5, true);
+    // Both columns are as good, the seed decides which one is played.
+    game.seed = Option::Some(1);
+    assert_eq!(play_for_ai(&game), Option::Some(5));
+
+    let played: Vec<u8> = (0..16)
+        .map(|seed| {
+            game.seed = Option::Some(seed);
+            play_for_ai(&game).unwrap()
+        })
+        .collect();
+    assert!(played.iter().all(|col| *col == 1 || *col == 5));
+    assert!(played.contains(&1) && played.contains(&5));
 }
 
 #[test]
 fn ai_should_play_col_4() {
     let mut game = Connect4::init_vs_ai(Difficulty::Normal);
 
-    game.drop_token_in_col(5);
-    game.drop_token_in_col(6);
-    game.drop_token_in_col(7);
+    game.drop_token_in_col(5).unwrap();
+    game.drop_token_in_col(6).unwrap();
+    game.drop_token_in_col(7).unwrap();
+
+    assert_eq!(play_for_ai(&game), Option::Some(4));
+}
+
+#[test]
+fn alpha_beta_matches_plain_minimax_value() {
+    for game in test_positions() {
+        for depth in 0..5 {
+            let mut table = TranspositionTable::init_default();
+            let pruned = minimax_run(
+                &mut game.clone(),
+                Procedure::Maximising,
+                depth,
+                0,
+                Score::MIN,
+                Score::MAX,
+                &mut Search::init(&mut table, Option::None),
+            );
+            let reference = minimax_reference(&game, &Procedure::Maximising, depth, 0);
+
+            assert_eq!(Option::Some(pruned.1), reference);
+        }
+    }
+}
+
+#[test]
+fn alpha_beta_best_columns_match_plain_minimax() {
+    for game in test_positions() {
+        for depth in 0..5 {
+            let mut table = TranspositionTable::init_default();
+            let scores =
+                root_column_scores(&game, depth, &mut Search::init(&mut table, Option::None));
+            let best = scores.iter().map(|w| w.1).max().unwrap();
+
+            // Every column the AI may pick has to be as good as plain minimax
+            // says the position is, and must have exactly that score.
+            scores
+                .iter()
+                .filter(|w| w.1 == best)
+                .for_each(|(col, score)| {
+                    let mut game_copy = game.clone();
+                    game_copy.drop_token_in_col(*col).unwrap();
+                    let reference =
+                        reference_token_drop(&mut game_copy, &Procedure::Maximising, depth, 1);
+
+                    assert_eq!(*score, reference);
+                    assert_eq!(
+                        Option::Some(reference),
+                        minimax_reference(&game, &Procedure::Maximising, depth, 0)
+                    );
+                });
+        }
+    }
+}
+
+#[test]
+fn table_is_reused_between_searches() {
+    use crate::transposition::ReplacementPolicy;
+
+    // A tiny table, shared across all depths of a position, forces plenty of
+    // collisions and stale entries, none of which should change the result.
+    // Positions get a table of their own, as one of them can be searched
+    // deeper from another, and deeper entries do change the result.
+    for game in test_positions() {
+        let mut table = TranspositionTable::init(61, ReplacementPolicy::Always);
+        for depth in 0..5 {
+            let scores =
+                root_column_scores(&game, depth, &mut Search::init(&mut table, Option::None));
+            let best = scores.iter().map(|w| w.1).max();
+
+            assert_eq!(
+                best,
+                minimax_reference(&game, &Procedure::Maximising, depth, 0)
+            );
+        }
+    }
+}
+
+#[test]
+fn deepening_goes_all_the_way_with_time_to_spare() {
+    use crate::clock::FakeClock;
+
+    // Clock that never moves, so the budget can't run out.
+    let clock = FakeClock::init(0.0);
+
+    for game in test_positions() {
+        let (col, depth) = iterative_deepening(
+            &game,
+            4,
+            1.0,
+            &clock,
+            &mut TranspositionTable::init_default(),
+        );
+        let scores = root_column_scores(
+            &game,
+            4,
+            &mut Search::init(&mut TranspositionTable::init_default(), Option::None),
+        );
+        let best = scores.iter().map(|w| w.1).max();
+
+        assert_eq!(depth, 4);
+        assert_eq!(
+            scores
+                .iter()
+                .find(|w| Option::Some(w.0) == col)
+                .map(|w| w.1),
+            best
+        );
+    }
+}
+
+#[test]
+fn deepening_stops_when_budget_runs_out() {
+    use crate::clock::FakeClock;
+
+    // Difficulty without the book, which would play right away.
+    let game = Connect4::init_vs_ai(Difficulty::VeryHard);
+
+    // Every look at the clock takes a millisecond, so the budget is gone well
+    // before the deepest search could finish.
+    let clock = FakeClock::init(1.0);
+    let (col, depth) = iterative_deepening(
+        &game,
+        10,
+        50.0,
+        &clock,
+        &mut TranspositionTable::init_default(),
+    );
+
+    assert!(depth < 10);
+    assert!(col.is_some_and(|c| (1..=7).contains(&c)));
+
+    // With no time at all, the shallowest search still picks a move.
+    let clock = FakeClock::init(1000.0);
+    let (col, depth) = iterative_deepening(
+        &game,
+        10,
+        0.0,
+        &clock,
+        &mut TranspositionTable::init_default(),
+    );
+
+    assert_eq!(depth, 0);
+    assert!(col.is_some_and(|c| (1..=7).contains(&c)));
+}
+
+#[test]
+fn deepening_takes_forced_win_from_finished_depth() {
+    use crate::clock::FakeClock;
+
+    let mut game = Connect4::init_vs_ai(Difficulty::Normal);
+    game.drop_token_in_col(3).unwrap();
+    game.drop_token_in_col(3).unwrap();
+    game.drop_token_in_col(3).unwrap();
+
+    let clock = FakeClock::init(1.0);
+    assert_eq!(play_for_ai_within(&game, 5.0, &clock), Option::Some(3));
+}
+
+#[test]
+fn ai_prefers_the_centre_on_empty_board() {
+    let game = Connect4::init_vs_ai(Difficulty::Test);
+    assert_eq!(play_for_ai(&game), Option::Some(4));
+}
+
+#[test]
+fn ai_blocks_open_three_beyond_its_horizon() {
+    // Player one threatens to get an open three on the bottom row, which the
+    // AI only sees as a loss a few moves later. Heuristics flag it right away.
+    let mut game = Connect4::init_vs_ai(Difficulty::Test);
+    [3, 7, 4].iter().for_each(|col| {
+        game.drop_token_in_col(*col).unwrap();
+        game.switch_players();
+    });
+
+    let played = play_for_ai(&game);
+    assert!(played == Option::Some(2) || played == Option::Some(5));
+}
+
+#[test]
+fn ai_takes_the_draw_when_nothing_else_is_left() {
+    // Last free cell in the board, which doesn't win for anyone.
+    let game = drawn_game(Difficulty::Hard, 1);
+
+    let scores = root_column_scores(
+        &game,
+        5,
+        &mut Search::init(&mut TranspositionTable::init_default(), Option::None),
+    );
+    assert_eq!(scores, vec![(7, Score::DRAW)]);
+    assert_eq!(play_for_ai(&game), Option::Some(7));
+}
+
+#[test]
+fn progress_is_reported_after_every_depth() {
+    use crate::clock::FakeClock;
+
+    let mut game = Connect4::init_vs_ai(Difficulty::Depth(4));
+    [4, 4, 3].iter().for_each(|col| {
+        game.drop_token_in_col(*col).unwrap();
+        game.switch_players();
+    });
+
+    let mut reported: Vec<Progress> = vec![];
+    let col = play_for_ai_controlled(
+        &game,
+        &mut TranspositionTable::init_default(),
+        &FakeClock::init(0.0),
+        Option::None,
+        &mut |progress| reported.push(*progress),
+    );
+
+    assert_eq!(
+        reported.iter().map(|p| p.depth).collect::<Vec<u8>>(),
+        vec![0, 1, 2, 3, 4]
+    );
+    assert!(reported.windows(2).all(|w| w[0].nodes < w[1].nodes));
+    assert_eq!(reported.last().unwrap().best_col, col);
+}
+
+#[test]
+fn cancelled_search_plays_the_best_move_so_far() {
+    use crate::clock::FakeClock;
+
+    // Difficulty without the book, which would play right away.
+    let game = Connect4::init_vs_ai(Difficulty::VeryHard);
+    let cancel = CancelToken::init();
+
+    let mut reported: Vec<Progress> = vec![];
+    let col = play_for_ai_controlled(
+        &game,
+        &mut TranspositionTable::init_default(),
+        &FakeClock::init(0.0),
+        Option::Some(&cancel),
+        &mut |progress| {
+            reported.push(*progress);
+            if progress.depth == 2 {
+                cancel.cancel();
+            }
+        },
+    );
+
+    assert_eq!(reported.last().unwrap().depth, 2);
+    assert_eq!(reported.last().unwrap().best_col, col);
+    assert!(col.is_some());
+}
+
+#[test]
+fn analysis_agrees_with_plain_minimax() {
+    test_positions().iter().for_each(|game| {
+        let depth = 3;
+        let scores = analyze_columns(game, depth, &mut TranspositionTable::init_default());
+
+        scores.iter().for_each(|(col, score)| {
+            let mut game_copy = game.clone();
+            game_copy.drop_token_in_col(*col).unwrap();
+            let expected = reference_token_drop(&mut game_copy, &Procedure::Maximising, depth, 1);
+            assert_eq!(*score, expected);
+        });
+    });
+}
+
+#[test]
+fn wins_score_the_same_at_any_depth() {
+    let mut game = Connect4::init_vs_ai(Difficulty::Normal);
+    game.drop_token_in_col(3).unwrap();
+    game.drop_token_in_col(3).unwrap();
+    game.drop_token_in_col(3).unwrap();
+
+    // Table is shared, so scores found by shallower searches are reused by
+    // the deeper ones.
+    let mut table = TranspositionTable::init_default();
+    (0..6).for_each(|depth| {
+        let scores = analyze_columns(&game, depth, &mut table);
+        assert_eq!(scores[2], (3, Score::win_in(1)));
+    });
+}
+
+#[test]
+fn same_seed_plays_the_same_moves() {
+    use crate::clock::FakeClock;
+
+    // Shallow searches leave plenty of ties to break.
+    for mut game in test_positions() {
+        game.difficulty = Difficulty::Test;
+        game.seed = Option::Some(42);
+        let played = play_for_ai(&game);
+
+        (0..5).for_each(|_| {
+            assert_eq!(play_for_ai(&game), played);
+            assert_eq!(
+                play_for_ai_within(&game, 1.0, &FakeClock::init(0.0)),
+                played
+            );
+        });
+    }
+}
+
+#[test]
+fn perfect_ai_plays_a_winning_move() {
+    let mut game = Connect4::init_vs_ai(Difficulty::Perfect);
+    "7422341735647741166133573473242566"
+        .chars()
+        .for_each(|col| {
+            game.drop_token_in_col(col.to_digit(10).unwrap() as u8)
+                .unwrap();
+            game.switch_players();
+        });
+    assert!(Solver::init().solve(&game).is_win());
+
+    // Whatever it plays, the opponent is left with a lost game.
+    let col = play_for_ai(&game).unwrap();
+    game.drop_token_in_col(col).unwrap();
+    game.switch_players();
+    assert!(Solver::init().solve(&game).is_loss());
+
+    // Solver runs in a single step, which can't be left unfinished.
+    let mut deepening = Deepening::init(&game, 42);
+    assert!(deepening.step(
+        &game,
+        &mut TranspositionTable::init_default(),
+        Option::None,
+        Option::None
+    ));
+    assert!(deepening.is_done());
+}
+
+#[test]
+fn ai_plays_from_the_book() {
+    // Player one took the centre, which the book knows wins for them.
+    let mut game = game_after(Difficulty::NoChance, "4");
+    let from_book = lookup(&game).unwrap();
+    assert_eq!(from_book.score, Score::loss_in(40));
+    assert_eq!(play_for_ai(&game), Option::Some(from_book.col));
+
+    let mut deepening = Deepening::init(&game, 10);
+    deepening.step(
+        &game,
+        &mut TranspositionTable::init_default(),
+        Option::None,
+        Option::None,
+    );
+    assert!(deepening.is_done());
+
+    // Weaker difficulties search for their own move.
+    game.difficulty = Difficulty::Hard;
+    assert_eq!(book_move(&game), Option::None);
+}
+
+#[test]
+fn straying_personalities_leave_the_book() {
+    use crate::personality::str_to_personality;
+
+    let mut game = game_after(Difficulty::NoChance, "4");
+    game.personality = str_to_personality("perfectionist").ok();
+    assert!(book_move(&game).is_some());
+
+    // Book only has the best column, with nothing for a beginner to stray to.
+    game.personality = str_to_personality("beginner").ok();
+    assert_eq!(book_move(&game), Option::None);
+    let mut table = TranspositionTable::init_default();
+    let mut search = Search::init(&mut table, Option::None);
+    assert_eq!(search_columns(&game, 2, &mut search).len(), 7);
+}
+
+#[test]
+fn mirrored_positions_share_the_table() {
+    let game = game_after(Difficulty::Hard, "122476");
+    let mirrored = game_after(Difficulty::Hard, "766412");
+
+    // Mirror image scores the same, column for mirrored column.
+    let mut table = TranspositionTable::init_default();
+    let scores = analyze_columns(&game, 5, &mut table);
+    let mut mirrored_scores: Vec<(u8, Score)> = analyze_columns(&mirrored, 5, &mut table)
+        .iter()
+        .map(|(col, score)| (mirror_col(*col), *score))
+        .collect();
+    mirrored_scores.reverse();
+    assert_eq!(mirrored_scores, scores);
 
-    assert_eq!(play_for_ai(&game), 4);
+    // Once the position is searched, most of its mirror image is found in the
+    // table.
+    let nodes_with = |table: &mut TranspositionTable| {
+        let mut search = Search::init(table, Option::None);
+        root_column_scores(&mirrored, 5, &mut search);
+        search.nodes
+    };
+    let mut table = TranspositionTable::init_default();
+    let fresh = nodes_with(&mut table);
+    table.clear();
+    root_column_scores(&game, 5, &mut Search::init(&mut table, Option::None));
+    assert!(nodes_with(&mut table) < fresh / 2);
+}
+
+#[test]
+fn deeper_entries_answer_shallower_searches() {
+    let game = game_after(Difficulty::Hard, "4453");
+    let (key, _) = game.get_canonical_key();
+    let search_with = |bound: Bound, score: Score, beta: Score| {
+        let mut table = TranspositionTable::init_default();
+        table.store(Entry {
+            key,
+            depth: 6,
+            score,
+            bound,
+            best_col: 4,
+        });
+        let mut search = Search::init(&mut table, Option::None);
+        minimax_run(
+            &mut game.clone(),
+            Procedure::Maximising,
+            2,
+            0,
+            Score::MIN,
+            beta,
+            &mut search,
+        )
+        .1
+    };
+    let stored = Score::from_heuristic(42);
+
+    // Exact score of a deeper search is taken as it is.
+    assert_eq!(search_with(Bound::Exact, stored, Score::MAX), stored);
+
+    // Bounds only end the search when they close the window.
+    let beta = Score::from_heuristic(10);
+    assert_eq!(search_with(Bound::Lower, stored, beta), stored);
+    assert_eq!(
+        search_with(Bound::Lower, Score::from_heuristic(-500), Score::MAX),
+        minimax_reference(&game, &Procedure::Maximising, 2, 0).unwrap()
+    );
+}
+
+#[test]
+fn search_leaves_the_game_as_it_was() {
+    for game in test_positions() {
+        let mut searched = game.clone();
+        let mut table = TranspositionTable::init_default();
+        minimax_run(
+            &mut searched,
+            Procedure::Maximising,
+            4,
+            0,
+            Score::MIN,
+            Score::MAX,
+            &mut Search::init(&mut table, Option::None),
+        );
+
+        assert_eq!(searched.get_key(), game.get_key());
+        assert_eq!(searched.get_current_player(), game.get_current_player());
+        assert_eq!(searched.get_outcome(), game.get_outcome());
+    }
 }
@@ -2,12 +2,32 @@
 
 /// Playing board!
 use crate::enums::board_column::{move_to_col_num, BoardColumn};
-use crate::enums::player::Player;
+use crate::enums::player::{player_to_json_str, Player};
+use crate::error::{Connect4Error, IllegalMove};
+use serde::{Deserialize, Serialize};
 
 /// Consts
 pub const ROW_NUM: i8 = 6;
 pub const COL_NUM: i8 = 7;
 
+/// Number of bits each column takes in a bitboard. There's one extra bit on top
+/// of every column, which is always empty, so shifting tokens of one column
+/// can never make them look like they're in the next one.
+pub const COL_HEIGHT: i8 = ROW_NUM + 1;
+
+/// Bit shifts moving a cell to its neighbour up, diagonally down and to the
+/// right, to the right, and diagonally up and to the right.
+const LINE_DIRECTIONS: [i8; 4] = [1, COL_HEIGHT - 1, COL_HEIGHT, COL_HEIGHT + 1];
+
+/// Position of a single cell on the board, counting columns from the left
+/// and rows from the bottom, both starting at zero, the same way the board
+/// is indexed in the input.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Cell {
+    pub col: u8,
+    pub row: u8,
+}
+
 /// Define if a position on the board was played or not, and by whom
 #[derive(Debug, Clone, PartialEq)]
 pub enum MovePlayed {
@@ -15,132 +35,362 @@ pub enum MovePlayed {
     NotPlayed,
 }
 
-/// Indicates success or failure when we try to drop a token
-#[derive(Debug, PartialEq)]
-pub enum MoveSuccess {
-    Placed(usize),
-    ColumnFull,
-    UnknownColumn,
-}
+type BoardState = Vec<Vec<MovePlayed>>;
 
-pub fn is_successful_move(m: &MoveSuccess) -> bool {
-    match m {
-        MoveSuccess::Placed(_) => true,
-        _ => false,
-    }
-}
+/// Bitboard of the tokens played by one player. Bit `col * COL_HEIGHT + row`
+/// is set if the player has a token on that position.
+pub type Bitboard = u64;
 
-type BoardState = Vec<Vec<MovePlayed>>;
+/// Bitboard with the bottom cell of every column set.
+pub const BOTTOM_MASK: Bitboard = bottom_mask();
 
-#[derive(Debug, Clone)]
+/// Bitboard with all the cells of the board set, leaving out the extra bit on
+/// top of each column.
+pub const BOARD_MASK: Bitboard = BOTTOM_MASK * ((1 << ROW_NUM) - 1);
+
+/// Board is kept as two bitboards, one for the player that plays first
+/// (`Player::One`), and one for their opponent, along with the number of tokens
+/// in each column. It's cheap to copy, so it can be cloned freely in the search.
+#[derive(Debug, Clone, Copy)]
 pub struct Board {
-    board_state: BoardState,
+    tokens: [Bitboard; 2],
+    heights: [u8; COL_NUM as usize],
+    opponent: Player,
 }
 
 impl Board {
     // Static init method!
     pub fn init() -> Board {
         Board {
-            board_state: vec![vec![MovePlayed::NotPlayed; ROW_NUM as usize]; COL_NUM as usize],
+            tokens: [0, 0],
+            heights: [0; COL_NUM as usize],
+            opponent: Player::AI,
         }
     }
 
     /// Initialises board from an input! TODO add a test!
-    pub fn init_from(str_board: Vec<Vec<String>>) -> Board {
-        str_board
-            .iter()
-            .enumerate()
-            .fold(Board::init(), |board, (i, col)| {
-                col.iter().enumerate().fold(board, |mut curr, (j, val)| {
-                    curr.board_state[i][j] = match val.as_str() {
-                        "player" => MovePlayed::Played(Player::One),
-                        "ai" => MovePlayed::Played(Player::AI),
-                        _ => MovePlayed::NotPlayed,
-                    };
-                    curr
-                })
+    pub fn init_from(str_board: &[Vec<String>]) -> Board {
+        str_board.iter().take(COL_NUM as usize).enumerate().fold(
+            Board::init(),
+            |board, (i, col)| {
+                col.iter()
+                    .take(ROW_NUM as usize)
+                    .enumerate()
+                    .fold(board, |mut curr, (j, val)| {
+                        let player = match val.as_str() {
+                            "player" => Player::One,
+                            "ai" => Player::AI,
+                            _ => return curr,
+                        };
+                        curr.tokens[player_idx(&player)] |= cell_bit(i, j);
+                        curr.heights[i] = j as u8 + 1;
+                        curr
+                    })
+            },
+        )
+    }
+
+    /// Board in the same format it's read from by `init_from`, leaving out
+    /// the empty cells.
+    pub fn get_input_board(&self) -> Vec<Vec<String>> {
+        (0..COL_NUM as usize)
+            .map(|col| {
+                (0..self.heights[col] as usize)
+                    .map(|row| {
+                        let player = if self.tokens[0] & cell_bit(col, row) != 0 {
+                            Player::One
+                        } else {
+                            Player::AI
+                        };
+                        String::from(player_to_json_str(&player))
+                    })
+                    .collect()
             })
+            .collect()
     }
 
     pub fn get_board_state(self) -> BoardState {
-        self.board_state
+        (0..COL_NUM as usize)
+            .map(|col| {
+                (0..ROW_NUM as usize)
+                    .map(|row| {
+                        let bit = cell_bit(col, row);
+                        if self.tokens[0] & bit != 0 {
+                            MovePlayed::Played(Player::One)
+                        } else if self.tokens[1] & bit != 0 {
+                            MovePlayed::Played(self.opponent)
+                        } else {
+                            MovePlayed::NotPlayed
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Takes the top token out of the column, the opposite of `drop_token`,
+    /// so moves can be tried out without copying the board. Returns the row
+    /// the token was taken from, or None if the column is empty.
+    pub fn take_token(&mut self, column: BoardColumn) -> Option<usize> {
+        let col_num: usize = move_to_col_num(column);
+        let row = self.heights.get(col_num)?.checked_sub(1)?;
+
+        let bit = cell_bit(col_num, row as usize);
+        self.tokens[0] &= !bit;
+        self.tokens[1] &= !bit;
+        self.heights[col_num] = row;
+        Option::Some(row as usize)
     }
 
     /// Method to check if a token can be dropped in a column.
     pub fn can_drop_token_in_col(&self, column: BoardColumn) -> bool {
         let col_num: usize = move_to_col_num(column);
 
-        if col_num >= COL_NUM as usize {
-            false
+        match self.heights.get(col_num) {
+            Option::Some(height) => *height < ROW_NUM as u8,
+            Option::None => false,
+        }
+    }
+
+    /// Bitboard with all tokens played by the player.
+    pub fn get_tokens_for_player(&self, player: &Player) -> Bitboard {
+        self.tokens[player_idx(player)]
+    }
+
+    /// Bitboard with all tokens played against the player.
+    pub fn get_opponent_tokens(&self, player: &Player) -> Bitboard {
+        self.tokens[1 - player_idx(player)]
+    }
+
+    /// Bitboard with all tokens on the board, regardless of who played them.
+    pub fn get_played_mask(&self) -> Bitboard {
+        self.tokens[0] | self.tokens[1]
+    }
+
+    /// Unique key of the position, including whose turn it is. Adding the
+    /// bottom row to the mask of played tokens leaves a single bit on top of
+    /// each column, so together with one player's tokens it tells exactly
+    /// where every token is.
+    pub fn get_key(&self, to_move: &Player) -> u64 {
+        self.get_position_key() | (player_idx(to_move) as u64) << 63
+    }
+
+    /// Key of the tokens on the board alone. In a game played by the rules,
+    /// the number of tokens tells whose turn it is anyway.
+    pub fn get_position_key(&self) -> u64 {
+        self.tokens[0] + self.get_played_mask() + BOTTOM_MASK
+    }
+
+    /// Key shared by the position and its mirror image, whichever of their
+    /// keys is lower, and whether it's the key of the mirror image. Mirrored
+    /// positions are worth the same, with the columns of every move mirrored.
+    pub fn get_canonical_key(&self, to_move: &Player) -> (u64, bool) {
+        let (key, mirrored) = self.get_canonical_position_key();
+        (key | (player_idx(to_move) as u64) << 63, mirrored)
+    }
+
+    /// Same as `get_canonical_key`, but for the tokens on the board alone.
+    pub fn get_canonical_position_key(&self) -> (u64, bool) {
+        let key = self.get_position_key();
+        // Sums in the key never carry over from one column to the next, so
+        // mirroring the key is the same as taking the key of the mirror image.
+        let mirrored_key = mirror_bitboard(key);
+        if mirrored_key < key {
+            (mirrored_key, true)
         } else {
-            self.board_state
-                .get(col_num)
-                .unwrap_or(&vec![])
-                .contains(&MovePlayed::NotPlayed)
+            (key, false)
         }
     }
 
-    /// Returns i64 representation of the current board state for a player. We
-    /// can then use bitwise operations to check if the player has won.
-    pub fn get_status_num_for_player(&self, player: &Player) -> u64 {
-        let total_cols: usize = COL_NUM as usize;
-        let palyed_move = MovePlayed::Played(player.clone());
+    /// Board mirrored about the centre column.
+    pub fn get_mirrored(&self) -> Board {
+        let mut heights = self.heights;
+        heights.reverse();
+        Board {
+            tokens: self.tokens.map(mirror_bitboard),
+            heights,
+            opponent: self.opponent,
+        }
+    }
 
-        self.board_state
+    /// Player whose turn it is, going by the number of tokens each player has.
+    /// Player one always moves first.
+    pub fn get_side_to_move(&self) -> Player {
+        if self.tokens[0].count_ones() == self.tokens[1].count_ones() {
+            Player::One
+        } else {
+            self.opponent
+        }
+    }
+
+    /// Number of tokens that can still be dropped before the board is full.
+    pub fn count_moves_left(&self) -> u8 {
+        (COL_NUM * ROW_NUM) as u8 - self.get_played_mask().count_ones() as u8
+    }
+
+    /// Checks if the player has four (or more) tokens in a row.
+    pub fn is_winner(&self, player: &Player) -> bool {
+        has_four_in_a_row(self.get_tokens_for_player(player))
+    }
+
+    /// Bitboard of all the player's tokens which are part of four (or more)
+    /// in a row. Empty if the player hasn't won.
+    pub fn get_winning_tokens(&self, player: &Player) -> Bitboard {
+        let tokens = self.get_tokens_for_player(player);
+
+        LINE_DIRECTIONS
             .iter()
-            .enumerate()
-            .fold(0, |acc, (col_idx, col)| {
-                acc + col.iter().enumerate().fold(0, |acc2, (row_idx, val)| {
-                    if let MovePlayed::Played(p) = val {
-                        if p == player {
-                            acc2 + (2 as u64).pow((row_idx * total_cols + col_idx) as u32)
-                        } else {
-                            acc2
-                        }
-                    } else {
-                        acc2
-                    }
+            .fold(0, |cells, &dir| cells | line_tokens(tokens, dir))
+    }
+
+    /// Cells of every line of four (or more) the player has, each line listed
+    /// from its bottom left end. Lines going in different directions can share
+    /// a cell, in which case it's part of both.
+    pub fn get_winning_lines(&self, player: &Player) -> Vec<Vec<Cell>> {
+        let tokens = self.get_tokens_for_player(player);
+
+        LINE_DIRECTIONS
+            .iter()
+            .flat_map(|&dir| {
+                let cells = line_tokens(tokens, dir);
+                // Line begins where the previous cell in its direction isn't
+                // part of a line.
+                let line_starts = cells & !(cells << dir);
+
+                bit_indexes(line_starts).map(move |start| {
+                    (0..)
+                        .map(|i| start + i * dir as u32)
+                        .take_while(|bit| cells & (1 << bit) != 0)
+                        .map(bit_to_cell)
+                        .collect()
                 })
             })
+            .collect()
+    }
+
+    /// There's no more space on the board to drop tokens.
+    pub fn is_full(&self) -> bool {
+        self.count_moves_left() == 0
+    }
+
+    /// Returns i64 representation of the current board state for a player, as
+    /// used by the win masks, where bit `row * COL_NUM + col` is set for every
+    /// token the player has played.
+    pub fn get_status_num_for_player(&self, player: &Player) -> u64 {
+        let tokens = self.get_tokens_for_player(player);
+
+        (0..COL_NUM as usize)
+            .flat_map(|col| (0..ROW_NUM as usize).map(move |row| (col, row)))
+            .filter(|(col, row)| tokens & cell_bit(*col, *row) != 0)
+            .fold(0, |acc, (col, row)| {
+                acc | 1 << (row * COL_NUM as usize + col)
+            })
     }
 
     /// Method that "drops" a token in the column, and marks it as played by player.
-    pub fn drop_token(&mut self, player: &Player, column: BoardColumn) -> MoveSuccess {
+    /// Returns the index of the row the token landed on.
+    pub fn drop_token(
+        &mut self,
+        player: &Player,
+        column: BoardColumn,
+    ) -> Result<usize, Connect4Error> {
         // Num of the colum to play!
         let col_num: usize = move_to_col_num(column);
 
-        match self.board_state.get_mut(col_num) {
-            Option::Some(col_played) => {
-                // Find the index where we need to put the token to!
-                let not_played_idx: Option<usize> =
-                    col_played.iter().position(|m| *m == MovePlayed::NotPlayed);
-
-                match not_played_idx {
-                    Option::Some(idx) => {
-                        // "Drop" token!
-                        self.board_state[col_num] = col_played
-                            .iter()
-                            .enumerate()
-                            .map(|(i, val)| {
-                                if i == idx {
-                                    MovePlayed::Played(player.clone())
-                                } else {
-                                    val.clone()
-                                }
-                            })
-                            .collect();
-
-                        // Return info about successful token drop with row idx.
-                        MoveSuccess::Placed(idx)
-                    }
-                    Option::None => MoveSuccess::ColumnFull,
+        match self.heights.get(col_num) {
+            Option::Some(&height) if height < ROW_NUM as u8 => {
+                // "Drop" token!
+                let idx = player_idx(player);
+                self.tokens[idx] |= cell_bit(col_num, height as usize);
+                self.heights[col_num] = height + 1;
+                if idx == 1 {
+                    self.opponent = *player;
                 }
+
+                // Return info about successful token drop with row idx.
+                Ok(height as usize)
             }
-            Option::None => MoveSuccess::UnknownColumn,
+            Option::Some(_) => Err(Connect4Error::IllegalMove {
+                col: col_num as u8 + 1,
+                reason: IllegalMove::ColumnFull,
+            }),
+            Option::None => Err(Connect4Error::IllegalMove {
+                col: col_num as u8 + 1,
+                reason: IllegalMove::UnknownColumn,
+            }),
         }
     }
 }
 
+/// Bit representing a single cell of the board.
+pub fn cell_bit(col: usize, row: usize) -> Bitboard {
+    1 << (col * COL_HEIGHT as usize + row)
+}
+
+const fn bottom_mask() -> Bitboard {
+    let mut mask = 0;
+    let mut col = 0;
+    while col < COL_NUM as usize {
+        mask |= 1 << (col * COL_HEIGHT as usize);
+        col += 1;
+    }
+    mask
+}
+
+/// Mirrors the bitboard about the centre column.
+pub fn mirror_bitboard(bitboard: Bitboard) -> Bitboard {
+    let column_bits = (1 << COL_HEIGHT) - 1;
+    (0..COL_NUM as usize).fold(0, |mirrored, col| {
+        let column = (bitboard >> (col * COL_HEIGHT as usize)) & column_bits;
+        mirrored | column << ((COL_NUM as usize - 1 - col) * COL_HEIGHT as usize)
+    })
+}
+
+/// Column in the same place on the mirrored board, counted from one.
+pub fn mirror_col(col: u8) -> u8 {
+    COL_NUM as u8 + 1 - col
+}
+
+/// Checks for four tokens in a row by shifting the bitboard in each direction
+/// (vertical, diagonal down, horizontal, diagonal up). Bits that are still set
+/// after overlapping the board with itself three times mark the start of a line.
+pub fn has_four_in_a_row(tokens: Bitboard) -> bool {
+    LINE_DIRECTIONS.iter().any(|&dir| {
+        let pairs = tokens & (tokens >> dir);
+        pairs & (pairs >> (2 * dir)) != 0
+    })
+}
+
+/// Tokens which are part of four (or more) in a row in one direction.
+fn line_tokens(tokens: Bitboard, dir: i8) -> Bitboard {
+    // Bits marking where a line of four starts.
+    let pairs = tokens & (tokens >> dir);
+    let starts = pairs & (pairs >> (2 * dir));
+
+    (0..4).fold(0, |acc, i| acc | starts << (i * dir))
+}
+
+/// Indexes of all bits that are set, from the lowest one.
+fn bit_indexes(bitboard: Bitboard) -> impl Iterator<Item = u32> {
+    (0..Bitboard::BITS).filter(move |bit| bitboard & (1 << bit) != 0)
+}
+
+fn bit_to_cell(bit: u32) -> Cell {
+    Cell {
+        col: (bit / COL_HEIGHT as u32) as u8,
+        row: (bit % COL_HEIGHT as u32) as u8,
+    }
+}
+
+/// Player one always gets the first bitboard, and whoever plays against them
+/// gets the second one.
+fn player_idx(player: &Player) -> usize {
+    match player {
+        Player::One => 0,
+        Player::Two | Player::AI => 1,
+    }
+}
+
 // TEST!!
 
 #[test]
@@ -149,10 +399,10 @@ fn can_drop_tokens() {
     let success = board.drop_token(&Player::One, BoardColumn::Col1);
 
     // Test placing first token by player one, on row index 0
-    assert_eq!(success, MoveSuccess::Placed(0));
-    assert_eq!(board.can_drop_token_in_col(BoardColumn::Col1), true);
+    assert_eq!(success, Ok(0));
+    assert!(board.can_drop_token_in_col(BoardColumn::Col1));
     assert_eq!(
-        *board.board_state.get(0).unwrap(),
+        *board.get_board_state().first().unwrap(),
         vec![
             MovePlayed::Played(Player::One),
             MovePlayed::NotPlayed,
@@ -165,10 +415,10 @@ fn can_drop_tokens() {
 
     // Test placing second token by player two, on row index 1
     let success = board.drop_token(&Player::Two, BoardColumn::Col1);
-    assert_eq!(success, MoveSuccess::Placed(1));
-    assert_eq!(board.can_drop_token_in_col(BoardColumn::Col1), true);
+    assert_eq!(success, Ok(1));
+    assert!(board.can_drop_token_in_col(BoardColumn::Col1));
     assert_eq!(
-        *board.board_state.get(0).unwrap(),
+        *board.get_board_state().first().unwrap(),
         vec![
             MovePlayed::Played(Player::One),
             MovePlayed::Played(Player::Two),
@@ -180,40 +430,302 @@ fn can_drop_tokens() {
     );
 
     // Drop more tokens to fill the first column
-    board.drop_token(&Player::One, BoardColumn::Col1);
-    board.drop_token(&Player::Two, BoardColumn::Col1);
-    board.drop_token(&Player::One, BoardColumn::Col1);
-    board.drop_token(&Player::Two, BoardColumn::Col1);
+    board.drop_token(&Player::One, BoardColumn::Col1).unwrap();
+    board.drop_token(&Player::Two, BoardColumn::Col1).unwrap();
+    board.drop_token(&Player::One, BoardColumn::Col1).unwrap();
+    board.drop_token(&Player::Two, BoardColumn::Col1).unwrap();
 
     // Try to drop one more!
     let success = board.drop_token(&Player::One, BoardColumn::Col1);
-    assert_eq!(success, MoveSuccess::ColumnFull);
-    assert_eq!(board.can_drop_token_in_col(BoardColumn::Col1), false);
+    assert_eq!(
+        success,
+        Err(Connect4Error::IllegalMove {
+            col: 1,
+            reason: IllegalMove::ColumnFull
+        })
+    );
+    assert!(!board.can_drop_token_in_col(BoardColumn::Col1));
 }
 
 #[test]
 fn can_calc_correct_player_status() {
     let mut board = Board::init();
-    board.drop_token(&Player::One, BoardColumn::Col1);
-    board.drop_token(&Player::One, BoardColumn::Col1);
-    board.drop_token(&Player::One, BoardColumn::Col1);
-    board.drop_token(&Player::One, BoardColumn::Col1);
+    board.drop_token(&Player::One, BoardColumn::Col1).unwrap();
+    board.drop_token(&Player::One, BoardColumn::Col1).unwrap();
+    board.drop_token(&Player::One, BoardColumn::Col1).unwrap();
+    board.drop_token(&Player::One, BoardColumn::Col1).unwrap();
 
     // First column should be a win for player one
     assert_eq!(board.get_status_num_for_player(&Player::One), 2113665);
 
     let mut board = Board::init();
-    board.drop_token(&Player::One, BoardColumn::Col1);
-    board.drop_token(&Player::Two, BoardColumn::Col2);
-    board.drop_token(&Player::One, BoardColumn::Col2);
-    board.drop_token(&Player::Two, BoardColumn::Col3);
-    board.drop_token(&Player::One, BoardColumn::Col4);
-    board.drop_token(&Player::Two, BoardColumn::Col3);
-    board.drop_token(&Player::One, BoardColumn::Col3);
-    board.drop_token(&Player::Two, BoardColumn::Col4);
-    board.drop_token(&Player::One, BoardColumn::Col5);
-    board.drop_token(&Player::Two, BoardColumn::Col4);
-    board.drop_token(&Player::One, BoardColumn::Col4);
+    board.drop_token(&Player::One, BoardColumn::Col1).unwrap();
+    board.drop_token(&Player::Two, BoardColumn::Col2).unwrap();
+    board.drop_token(&Player::One, BoardColumn::Col2).unwrap();
+    board.drop_token(&Player::Two, BoardColumn::Col3).unwrap();
+    board.drop_token(&Player::One, BoardColumn::Col4).unwrap();
+    board.drop_token(&Player::Two, BoardColumn::Col3).unwrap();
+    board.drop_token(&Player::One, BoardColumn::Col3).unwrap();
+    board.drop_token(&Player::Two, BoardColumn::Col4).unwrap();
+    board.drop_token(&Player::One, BoardColumn::Col5).unwrap();
+    board.drop_token(&Player::Two, BoardColumn::Col4).unwrap();
+    board.drop_token(&Player::One, BoardColumn::Col4).unwrap();
 
     assert_eq!(board.get_status_num_for_player(&Player::One), 16843033);
 }
+
+#[test]
+fn can_detect_wins_in_all_directions() {
+    // Vertical, horizontal and both diagonals, given as (col, row) cells.
+    let lines: Vec<Vec<(usize, usize)>> = vec![
+        vec![(0, 0), (0, 1), (0, 2), (0, 3)],
+        vec![(2, 5), (3, 5), (4, 5), (5, 5)],
+        vec![(3, 0), (4, 1), (5, 2), (6, 3)],
+        vec![(0, 5), (1, 4), (2, 3), (3, 2)],
+    ];
+
+    for line in lines {
+        let tokens = line.iter().fold(0, |acc, (c, r)| acc | cell_bit(*c, *r));
+        assert!(has_four_in_a_row(tokens));
+
+        // Removing any one of the tokens breaks the line.
+        for (c, r) in line.iter() {
+            assert!(!has_four_in_a_row(tokens & !cell_bit(*c, *r)));
+        }
+    }
+}
+
+#[test]
+fn lines_do_not_wrap_between_columns() {
+    // Top three of the first column, and bottom of the second one.
+    let vertical = cell_bit(0, 3) | cell_bit(0, 4) | cell_bit(0, 5) | cell_bit(1, 0);
+    // Diagonal running off the top of the board into the next column.
+    let diagonal = cell_bit(0, 4) | cell_bit(1, 5) | cell_bit(2, 0) | cell_bit(3, 1);
+
+    assert!(!has_four_in_a_row(vertical));
+    assert!(!has_four_in_a_row(diagonal));
+}
+
+#[test]
+fn bitboard_wins_agree_with_win_masks() {
+    use crate::models::masks::get_cached_win_masks;
+
+    let mut board = Board::init();
+    let moves = [4, 4, 3, 5, 2, 2, 5, 3, 6, 1, 1, 6, 7, 7, 7];
+
+    for (i, col) in moves.iter().enumerate() {
+        let player = if i % 2 == 0 { Player::One } else { Player::AI };
+        let column = crate::enums::board_column::num_to_col_num(*col).unwrap();
+        board.drop_token(&player, column).unwrap();
+
+        let status = board.get_status_num_for_player(&player);
+        let by_masks = get_cached_win_masks().into_iter().any(|m| status & m == m);
+        assert_eq!(board.is_winner(&player), by_masks);
+    }
+}
+
+#[test]
+fn can_init_from_input() {
+    let input = vec![
+        vec![String::from("player"), String::from("ai")],
+        vec![],
+        vec![String::from("ai")],
+    ];
+    let board = Board::init_from(&input);
+    let state = board.get_board_state();
+
+    assert_eq!(state[0][0], MovePlayed::Played(Player::One));
+    assert_eq!(state[0][1], MovePlayed::Played(Player::AI));
+    assert_eq!(state[0][2], MovePlayed::NotPlayed);
+    assert_eq!(state[1][0], MovePlayed::NotPlayed);
+    assert_eq!(state[2][0], MovePlayed::Played(Player::AI));
+    assert!(board.can_drop_token_in_col(BoardColumn::Col1));
+}
+
+#[test]
+fn can_turn_board_back_into_input() {
+    let input = vec![
+        vec![String::from("player"), String::from("ai")],
+        vec![],
+        vec![String::from("player")],
+        vec![],
+        vec![],
+        vec![],
+        vec![],
+    ];
+    let board = Board::init_from(&input);
+
+    assert_eq!(board.get_input_board(), input);
+    assert_eq!(board.get_side_to_move(), Player::AI);
+    assert_eq!(Board::init().get_side_to_move(), Player::One);
+}
+
+#[test]
+fn same_position_has_the_same_key() {
+    use crate::enums::board_column::num_to_col_num;
+
+    let play = |moves: &[u8]| {
+        moves
+            .iter()
+            .enumerate()
+            .fold(Board::init(), |mut board, (i, col)| {
+                let player = if i % 2 == 0 { Player::One } else { Player::AI };
+                board
+                    .drop_token(&player, num_to_col_num(*col).unwrap())
+                    .unwrap();
+                board
+            })
+    };
+
+    // Same tokens, played in a different order.
+    let board = play(&[4, 3, 5, 3]);
+    let transposed = play(&[5, 3, 4, 3]);
+    assert_eq!(
+        board.get_key(&Player::One),
+        transposed.get_key(&Player::One)
+    );
+
+    // Different tokens, or a different player to move.
+    let swapped = play(&[3, 4, 3, 5]);
+    assert_ne!(board.get_key(&Player::One), swapped.get_key(&Player::One));
+    assert_ne!(board.get_key(&Player::One), board.get_key(&Player::AI));
+    assert_ne!(
+        play(&[]).get_key(&Player::One),
+        play(&[1]).get_key(&Player::One)
+    );
+}
+
+#[test]
+fn can_find_winning_tokens() {
+    use crate::enums::board_column::num_to_col_num;
+
+    let mut board = Board::init();
+    // Five in a row on the bottom, and a lone token on top.
+    [1, 2, 3, 4, 5].iter().for_each(|col| {
+        board
+            .drop_token(&Player::One, num_to_col_num(*col).unwrap())
+            .unwrap();
+    });
+    board.drop_token(&Player::One, BoardColumn::Col1).unwrap();
+    board.drop_token(&Player::AI, BoardColumn::Col7).unwrap();
+
+    let row = (0..5).fold(0, |acc, col| acc | cell_bit(col, 0));
+    assert_eq!(board.get_winning_tokens(&Player::One), row);
+    assert_eq!(board.get_winning_tokens(&Player::AI), 0);
+    assert!(!board.is_full());
+}
+
+#[test]
+fn can_list_winning_lines() {
+    use crate::enums::board_column::num_to_col_num;
+
+    let mut board = Board::init();
+    // Five in a row on the bottom, and four up the third column.
+    [1, 2, 3, 4, 5, 3, 3, 3].iter().for_each(|col| {
+        board
+            .drop_token(&Player::One, num_to_col_num(*col).unwrap())
+            .unwrap();
+    });
+
+    let cell = |col, row| Cell { col, row };
+    assert_eq!(
+        board.get_winning_lines(&Player::One),
+        vec![
+            vec![cell(2, 0), cell(2, 1), cell(2, 2), cell(2, 3)],
+            vec![cell(0, 0), cell(1, 0), cell(2, 0), cell(3, 0), cell(4, 0)],
+        ]
+    );
+    assert!(board.get_winning_lines(&Player::AI).is_empty());
+}
+
+#[test]
+fn can_list_diagonal_winning_lines() {
+    let mut board = Board::init();
+    let mut play = |player: Player, col: BoardColumn| {
+        board.drop_token(&player, col).unwrap();
+    };
+
+    // Staircase for the AI, going up to the right from column 4.
+    play(Player::One, BoardColumn::Col5);
+    play(Player::One, BoardColumn::Col6);
+    play(Player::One, BoardColumn::Col6);
+    play(Player::One, BoardColumn::Col7);
+    play(Player::One, BoardColumn::Col7);
+    play(Player::One, BoardColumn::Col7);
+    play(Player::AI, BoardColumn::Col4);
+    play(Player::AI, BoardColumn::Col5);
+    play(Player::AI, BoardColumn::Col6);
+    play(Player::AI, BoardColumn::Col7);
+
+    let cell = |col, row| Cell { col, row };
+    assert_eq!(
+        board.get_winning_lines(&Player::AI),
+        vec![vec![cell(3, 0), cell(4, 1), cell(5, 2), cell(6, 3)]]
+    );
+}
+
+#[test]
+fn mirrored_positions_share_a_key() {
+    use crate::enums::board_column::num_to_col_num;
+
+    let play = |moves: &[u8]| {
+        moves
+            .iter()
+            .enumerate()
+            .fold(Board::init(), |mut board, (i, col)| {
+                let player = if i % 2 == 0 { Player::One } else { Player::AI };
+                board
+                    .drop_token(&player, num_to_col_num(*col).unwrap())
+                    .unwrap();
+                board
+            })
+    };
+
+    let board = play(&[1, 2, 2, 4, 7]);
+    let mirrored = play(&[7, 6, 6, 4, 1]);
+    assert_eq!(
+        board.get_mirrored().get_key(&Player::AI),
+        mirrored.get_key(&Player::AI)
+    );
+    assert_eq!(
+        board.get_mirrored().get_mirrored().get_key(&Player::AI),
+        board.get_key(&Player::AI)
+    );
+
+    // Only one of the two is the mirror image of the canonical position.
+    let (key, is_mirrored) = board.get_canonical_key(&Player::AI);
+    assert_eq!(mirrored.get_canonical_key(&Player::AI), (key, !is_mirrored));
+    assert_ne!(key, play(&[1, 2, 2, 4, 6]).get_canonical_key(&Player::AI).0);
+
+    // Symmetric positions are their own mirror image.
+    let symmetric = play(&[4, 4, 3, 5]);
+    assert_eq!(
+        symmetric.get_canonical_position_key(),
+        (symmetric.get_position_key(), false)
+    );
+    assert_eq!(mirror_col(1), 7);
+    assert_eq!(mirror_col(4), 4);
+}
+
+#[test]
+fn taking_tokens_undoes_dropping_them() {
+    use crate::enums::board_column::num_to_col_num;
+
+    let mut board = Board::init();
+    board.drop_token(&Player::One, BoardColumn::Col4).unwrap();
+    let before = board.get_key(&Player::AI);
+
+    [3, 3, 5].iter().enumerate().for_each(|(i, col)| {
+        let player = if i % 2 == 0 { Player::AI } else { Player::One };
+        board
+            .drop_token(&player, num_to_col_num(*col).unwrap())
+            .unwrap();
+    });
+    assert_eq!(board.take_token(BoardColumn::Col5), Option::Some(0));
+    assert_eq!(board.take_token(BoardColumn::Col3), Option::Some(1));
+    assert_eq!(board.take_token(BoardColumn::Col3), Option::Some(0));
+    assert_eq!(board.take_token(BoardColumn::Col3), Option::None);
+
+    assert_eq!(board.get_key(&Player::AI), before);
+    assert_eq!(board.count_moves_left(), 41);
+}
@@ -89,9 +89,8 @@ pub fn get_cached_win_masks() -> Vec<WinMask> {
 pub fn get_win_masks() -> Vec<WinMask> {
     get_board_points()
         .iter()
-        .map(|bp| get_win_points(*bp))
-        .flatten()
-        .filter(|wp| wp.len() > 0)
+        .flat_map(|bp| get_win_points(*bp))
+        .filter(|wp| !wp.is_empty())
         .map(get_mask)
         .collect()
 }
@@ -99,7 +98,7 @@ pub fn get_win_masks() -> Vec<WinMask> {
 /// Function which takes in a vec of numbers and transforms them into a single
 /// number, by summing poweres of two of all numbers, thus creating a binary mask.
 pub fn get_mask(wp: BoardPoints) -> WinMask {
-    wp.iter().clone().map(|n| (2 as u64).pow(*n as u32)).sum()
+    wp.iter().map(|n| 2_u64.pow(*n as u32)).sum()
 }
 
 /***** MODULE PRIVATE FUNCTIONS *****/
@@ -118,8 +117,7 @@ fn get_cols_val(Cols::Cols(val): &Cols) -> i8 {
 /// Return a vector of all points on the connect4 playing board!
 fn get_board_points() -> Vec<BoardPoint> {
     (0..COL_NUM)
-        .map(|x| (0..ROW_NUM).map(move |y| (x as i8, y as i8)))
-        .flatten()
+        .flat_map(|x| (0..ROW_NUM).map(move |y| (x, y)))
         .collect()
 }
 
@@ -212,22 +210,22 @@ fn theres_42_board_points() {
 fn theres_these_points_on_board() {
     let points = get_board_points();
 
-    assert_eq!(points.contains(&(0, 0)), true);
-    assert_eq!(points.contains(&(4, 5)), true);
-    assert_eq!(points.contains(&(6, 5)), true);
-    assert_eq!(points.contains(&(1, 1)), true);
-    assert_eq!(points.contains(&(2, 5)), true);
-    assert_eq!(points.contains(&(6, 4)), true);
+    assert!(points.contains(&(0, 0)));
+    assert!(points.contains(&(4, 5)));
+    assert!(points.contains(&(6, 5)));
+    assert!(points.contains(&(1, 1)));
+    assert!(points.contains(&(2, 5)));
+    assert!(points.contains(&(6, 4)));
 }
 
 #[test]
 fn but_theres_no_these_points() {
     let points = get_board_points();
 
-    assert_eq!(points.contains(&(-1, 0)), false);
-    assert_eq!(points.contains(&(6, 6)), false);
-    assert_eq!(points.contains(&(7, -6)), false);
-    assert_eq!(points.contains(&(50, 20)), false);
+    assert!(!points.contains(&(-1, 0)));
+    assert!(!points.contains(&(6, 6)));
+    assert!(!points.contains(&(7, -6)));
+    assert!(!points.contains(&(50, 20)));
 }
 
 #[test]
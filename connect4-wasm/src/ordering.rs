@@ -0,0 +1,222 @@
+//! Order in which the minimax search tries the columns. Alpha-beta prunes the
+//! most when the best column is searched first, so columns likely to be best
+//! go first: the ones winning right away, the ones blocking a win of the
+//! opponent, the best column found by an earlier search of the position, and
+//! then the columns which cut the search short in other positions. The rest
+//! are searched from the centre out, as central columns are part of more
+//! lines.
+
+use crate::connect4::Connect4;
+use crate::enums::player::Player;
+use crate::evaluation::winning_cells;
+use crate::models::board::{Bitboard, BOARD_MASK, BOTTOM_MASK, COL_HEIGHT, COL_NUM, ROW_NUM};
+#[cfg(test)]
+use crate::test_support::game_after;
+
+/// Columns from the centre out, counted from one.
+const CENTRE_FIRST: [u8; COL_NUM as usize] = [4, 3, 5, 2, 6, 1, 7];
+
+/// Plies a search goes through, from zero on the root, to one past the last
+/// cell of the board.
+const MAX_PLIES: usize = (COL_NUM * ROW_NUM) as usize + 2;
+
+/// Priorities of the kinds of columns, each above all the ones after it.
+const WINNING: u32 = 1 << 30;
+const BLOCKING: u32 = 1 << 29;
+const TABLE_HINT: u32 = 1 << 28;
+const KILLER: u32 = 1 << 26; // Twice as much for the latest killer
+const MAX_HISTORY: u32 = KILLER - 1;
+
+/// Killer moves and history, learned from the cut-offs of a search.
+///
+/// Killer moves are the last two columns which cut the search short on a
+/// ply, as the same column often refutes the sibling positions too. History
+/// adds up the cut-offs of every column for each player, over the whole
+/// search, weighted by the depth left, as cut-offs close to the root save
+/// the most.
+#[derive(Debug, Clone)]
+pub struct MoveOrdering {
+    killers: [[u8; 2]; MAX_PLIES], // Zero when there's none
+    history: [[u32; COL_NUM as usize]; 2],
+    enabled: bool,
+}
+
+impl Default for MoveOrdering {
+    fn default() -> MoveOrdering {
+        MoveOrdering::init()
+    }
+}
+
+impl MoveOrdering {
+    pub fn init() -> MoveOrdering {
+        MoveOrdering {
+            killers: [[0; 2]; MAX_PLIES],
+            history: [[0; COL_NUM as usize]; 2],
+            enabled: true,
+        }
+    }
+
+    /// Columns are always searched from left to right, which is only useful
+    /// for comparing how much the ordering helps.
+    pub fn init_disabled() -> MoveOrdering {
+        MoveOrdering {
+            enabled: false,
+            ..MoveOrdering::init()
+        }
+    }
+
+    /// Playable columns of the game, counted from one, in the order they
+    /// should be searched, along with how many there are. Hint is the best
+    /// column found by an earlier search of the position.
+    pub fn order_columns(
+        &self,
+        game: &Connect4,
+        ply: u8,
+        hint: Option<u8>,
+    ) -> ([u8; COL_NUM as usize], usize) {
+        let board = game.get_board();
+        let played = board.get_played_mask();
+        let playable = (played + BOTTOM_MASK) & BOARD_MASK;
+        let mut ordered = [(0, 0); COL_NUM as usize];
+        let mut len = 0;
+
+        if !self.enabled {
+            (1..=COL_NUM as u8)
+                .filter(|col| playable & column_bits(*col) != 0)
+                .for_each(|col| {
+                    ordered[len] = (col, 0);
+                    len += 1;
+                });
+            return (ordered.map(|(col, _)| col), len);
+        }
+
+        let player = game.get_current_player();
+        let own = board.get_tokens_for_player(&player);
+        let wins = winning_cells(own, played);
+        let blocks = winning_cells(played ^ own, played);
+        let killers = self.killers.get(ply as usize).copied().unwrap_or([0; 2]);
+        let history = &self.history[side_idx(&player)];
+
+        CENTRE_FIRST.iter().for_each(|col| {
+            let cell = playable & column_bits(*col);
+            if cell == 0 {
+                return;
+            }
+
+            let priority = if cell & wins != 0 {
+                WINNING
+            } else if cell & blocks != 0 {
+                BLOCKING
+            } else if hint == Option::Some(*col) {
+                TABLE_HINT
+            } else if killers[0] == *col {
+                2 * KILLER
+            } else if killers[1] == *col {
+                KILLER
+            } else {
+                history[*col as usize - 1]
+            };
+
+            // Insertion sort, keeping columns of the same priority centre
+            // first.
+            let mut idx = len;
+            while idx > 0 && ordered[idx - 1].1 < priority {
+                ordered[idx] = ordered[idx - 1];
+                idx -= 1;
+            }
+            ordered[idx] = (*col, priority);
+            len += 1;
+        });
+
+        (ordered.map(|(col, _)| col), len)
+    }
+
+    /// Remembers the column which cut the search short on the ply, with the
+    /// depth that was left to search.
+    pub fn on_cutoff(&mut self, game: &Connect4, col: u8, ply: u8, depth: u8) {
+        if let Option::Some(killers) = self.killers.get_mut(ply as usize) {
+            if killers[0] != col {
+                killers[1] = killers[0];
+                killers[0] = col;
+            }
+        }
+
+        let history = &mut self.history[side_idx(&game.get_current_player())][col as usize - 1];
+        let bonus = (depth as u32 + 1) * (depth as u32 + 1);
+        *history = history.saturating_add(bonus).min(MAX_HISTORY);
+    }
+}
+
+/// All the cells of the column, counted from one.
+fn column_bits(col: u8) -> Bitboard {
+    ((1 << COL_HEIGHT) - 1) << ((col as usize - 1) * COL_HEIGHT as usize)
+}
+
+fn side_idx(player: &Player) -> usize {
+    match player {
+        Player::One => 0,
+        Player::Two | Player::AI => 1,
+    }
+}
+
+// Module tests!!
+
+#[test]
+fn centre_columns_go_first() {
+    use crate::enums::difficulty::Difficulty;
+
+    let (columns, len) = MoveOrdering::init().order_columns(
+        &Connect4::init_vs_ai(Difficulty::Hard),
+        1,
+        Option::None,
+    );
+    assert_eq!((columns, len), ([4, 3, 5, 2, 6, 1, 7], 7));
+
+    let (columns, len) = MoveOrdering::init_disabled().order_columns(
+        &Connect4::init_vs_ai(Difficulty::Hard),
+        1,
+        Option::None,
+    );
+    assert_eq!((columns, len), ([1, 2, 3, 4, 5, 6, 7], 7));
+
+    // Full columns are left out.
+    let game = game_after(Difficulty::Hard, "444444");
+    let (columns, len) = MoveOrdering::init().order_columns(&game, 1, Option::None);
+    assert_eq!(&columns[..len], &[3, 5, 2, 6, 1, 7]);
+}
+
+#[test]
+fn wins_and_blocks_go_before_anything_else() {
+    use crate::enums::difficulty::Difficulty;
+
+    // Player one has three in column 1, the AI has three in column 7, and it's
+    // player one's turn.
+    let game = game_after(Difficulty::Hard, "171717");
+    let mut ordering = MoveOrdering::init();
+    ordering.on_cutoff(&game, 3, 1, 4);
+
+    let (columns, _) = ordering.order_columns(&game, 1, Option::Some(5));
+    assert_eq!(&columns[..4], &[1, 7, 5, 3]);
+}
+
+#[test]
+fn killers_and_history_are_learned_from_cutoffs() {
+    use crate::enums::difficulty::Difficulty;
+
+    let game = Connect4::init_vs_ai(Difficulty::Hard);
+    let mut ordering = MoveOrdering::init();
+
+    // Latest killer of the ply goes first, and the other one after it.
+    ordering.on_cutoff(&game, 2, 3, 1);
+    ordering.on_cutoff(&game, 6, 3, 2);
+    let (columns, _) = ordering.order_columns(&game, 3, Option::None);
+    assert_eq!(&columns[..2], &[6, 2]);
+
+    // On other plies, columns with the most history go first.
+    let (columns, _) = ordering.order_columns(&game, 5, Option::None);
+    assert_eq!(&columns[..3], &[6, 2, 4]);
+
+    // History is kept for each player.
+    let (columns, _) = ordering.order_columns(&game_after(Difficulty::Hard, "4"), 5, Option::None);
+    assert_eq!(&columns[..3], &[4, 3, 5]);
+}
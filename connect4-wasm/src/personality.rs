@@ -0,0 +1,355 @@
+//! Personality of the AI, choosing between the columns once the search has
+//! scored them. Instead of always playing the best column, the AI can pick
+//! one at random, better columns being more likely, and now and then blunder,
+//! so a weak AI plays like a beginner rather than a perfect player with a
+//! short horizon. It still never misses a win in one, unless told to.
+//!
+//! Only engines scoring every column, minimax and greedy, play with a
+//! personality, and minimax leaves the book when it strays, as the book only
+//! holds the best column.
+
+use crate::error::Connect4Error;
+use crate::evaluation::WIN_WEIGHT;
+use crate::score::Score;
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+/// How the AI picks between the scored columns. The default one always plays
+/// the best column. Settings are checked by `init`, so they're always valid.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "PersonalityInput", into = "PersonalityInput")]
+pub struct Personality {
+    /// Temperature of the softmax over the scores of the columns, in heuristic
+    /// weight. A column that much worse than another is e times less likely
+    /// to be played. At zero the best column is always played.
+    temperature: f64,
+    /// Chance of playing any column but the best ones, however bad it is.
+    blunder_rate: f64,
+    /// Always plays a column winning right away, even when blundering.
+    takes_wins: bool,
+}
+
+impl Default for Personality {
+    fn default() -> Personality {
+        Personality {
+            temperature: 0.0,
+            blunder_rate: 0.0,
+            takes_wins: true,
+        }
+    }
+}
+
+/// Personality as it comes in the JSON input, either the name of one of the
+/// presets, or its settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PersonalityInput {
+    Name(String),
+    Settings(PersonalitySettings),
+}
+
+/// Settings of a personality, as they come in the JSON input, before they're
+/// checked. Missing ones are the ones of the default personality.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PersonalitySettings {
+    pub temperature: f64,
+    pub blunder_rate: f64,
+    pub takes_wins: bool,
+}
+
+impl Default for PersonalitySettings {
+    fn default() -> PersonalitySettings {
+        PersonalitySettings::from(Personality::default())
+    }
+}
+
+impl From<Personality> for PersonalitySettings {
+    fn from(personality: Personality) -> PersonalitySettings {
+        PersonalitySettings {
+            temperature: personality.temperature,
+            blunder_rate: personality.blunder_rate,
+            takes_wins: personality.takes_wins,
+        }
+    }
+}
+
+impl TryFrom<PersonalityInput> for Personality {
+    type Error = Connect4Error;
+
+    fn try_from(input: PersonalityInput) -> Result<Personality, Connect4Error> {
+        match input {
+            PersonalityInput::Name(name) => str_to_personality(&name),
+            PersonalityInput::Settings(settings) => Personality::init(
+                settings.temperature,
+                settings.blunder_rate,
+                settings.takes_wins,
+            ),
+        }
+    }
+}
+
+impl From<Personality> for PersonalityInput {
+    fn from(personality: Personality) -> PersonalityInput {
+        PersonalityInput::Settings(PersonalitySettings::from(personality))
+    }
+}
+
+/// Preset with the given name. A beginner strays from the best column often,
+/// and blunders every so often. A casual player does both less.
+pub fn str_to_personality(name: &str) -> Result<Personality, Connect4Error> {
+    match name {
+        "beginner" => Ok(Personality {
+            temperature: 12.0,
+            blunder_rate: 0.1,
+            takes_wins: true,
+        }),
+        "casual" => Ok(Personality {
+            temperature: 5.0,
+            blunder_rate: 0.03,
+            takes_wins: true,
+        }),
+        "perfectionist" => Ok(Personality::default()),
+        _ => Err(Connect4Error::UnknownPersonality {
+            name: String::from(name),
+        }),
+    }
+}
+
+impl Personality {
+    /// Personality with the given settings. Temperature can't be negative, and
+    /// the blunder rate is a chance, between 0 and 1.
+    pub fn init(
+        temperature: f64,
+        blunder_rate: f64,
+        takes_wins: bool,
+    ) -> Result<Personality, Connect4Error> {
+        if temperature.is_finite() && temperature >= 0.0 && (0.0..=1.0).contains(&blunder_rate) {
+            Ok(Personality {
+                temperature,
+                blunder_rate,
+                takes_wins,
+            })
+        } else {
+            Err(Connect4Error::UnknownPersonality {
+                name: String::from("settings"),
+            })
+        }
+    }
+
+    pub fn get_temperature(&self) -> f64 {
+        self.temperature
+    }
+
+    pub fn get_blunder_rate(&self) -> f64 {
+        self.blunder_rate
+    }
+
+    pub fn takes_wins(&self) -> bool {
+        self.takes_wins
+    }
+
+    /// Tells if columns other than the best ones can be played at all.
+    pub fn strays(&self) -> bool {
+        self.temperature > 0.0 || self.blunder_rate > 0.0
+    }
+
+    /// Tells if columns other than the best ones can be played on purpose,
+    /// and so need their exact scores, rather than the bounds the search
+    /// settles for.
+    pub fn needs_exact_scores(&self) -> bool {
+        self.temperature > 0.0
+    }
+
+    /// Column to play, out of the columns and their scores, counted from one.
+    pub fn choose_col(&self, column_scores: &[(u8, Score)], rng: &mut dyn RngCore) -> Option<u8> {
+        let best = column_scores.iter().map(|(_, score)| *score).max()?;
+
+        if self.takes_wins && best == Score::win_in(1) {
+            return column_scores
+                .iter()
+                .find(|(_, score)| *score == best)
+                .map(|(col, _)| *col);
+        }
+
+        let worse: Vec<u8> = column_scores
+            .iter()
+            .filter(|(_, score)| *score < best)
+            .map(|(col, _)| *col)
+            .collect();
+        if !worse.is_empty() && rng.gen_bool(self.blunder_rate) {
+            return worse.get(rng.gen_range(0..worse.len())).copied();
+        }
+
+        let best_value = score_to_value(best);
+        let chances: Vec<f64> = column_scores
+            .iter()
+            .map(|(_, score)| {
+                if *score == best {
+                    1.0
+                } else if self.temperature > 0.0 {
+                    ((score_to_value(*score) - best_value) / self.temperature).exp()
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+
+        // Picks the column the draw lands on, along all the chances laid end
+        // to end. Rounding can leave it past the end, on the best column.
+        let mut draw = rng.gen_range(0.0..chances.iter().sum::<f64>());
+        column_scores
+            .iter()
+            .zip(chances.iter())
+            .find(|(_, chance)| {
+                draw -= *chance;
+                draw < 0.0
+            })
+            .map(|((col, _), _)| *col)
+            .or_else(|| {
+                column_scores
+                    .iter()
+                    .find(|(_, score)| *score == best)
+                    .map(|(col, _)| *col)
+            })
+    }
+}
+
+/// Score as a heuristic weight, with proven wins and losses worth as much as
+/// a win can be.
+fn score_to_value(score: Score) -> f64 {
+    match score.get_heuristic() {
+        Option::Some(weight) => weight as f64,
+        Option::None if score.is_win() => WIN_WEIGHT as f64,
+        Option::None => -WIN_WEIGHT as f64,
+    }
+}
+
+// Module tests!!
+
+#[cfg(test)]
+fn count_choices(personality: &Personality, column_scores: &[(u8, Score)], games: u64) -> [u32; 8] {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    let mut counts = [0; 8];
+    (0..games).for_each(|seed| {
+        let col = personality
+            .choose_col(column_scores, &mut StdRng::seed_from_u64(seed))
+            .unwrap();
+        counts[col as usize] += 1;
+    });
+    counts
+}
+
+#[test]
+fn default_personality_plays_the_best_column() {
+    let scores = [
+        (1, Score::from_heuristic(-4)),
+        (4, Score::from_heuristic(9)),
+        (7, Score::loss_in(2)),
+    ];
+    assert_eq!(
+        count_choices(&Personality::default(), &scores, 50),
+        [0, 0, 0, 0, 50, 0, 0, 0]
+    );
+    assert_eq!(
+        Personality::default().choose_col(&[], &mut rand::thread_rng()),
+        Option::None
+    );
+}
+
+#[test]
+fn better_columns_are_more_likely() {
+    let scores = [
+        (2, Score::from_heuristic(0)),
+        (3, Score::from_heuristic(5)),
+        (4, Score::from_heuristic(10)),
+        (5, Score::loss_in(2)),
+    ];
+    let personality = Personality {
+        temperature: 5.0,
+        ..Personality::default()
+    };
+    let counts = count_choices(&personality, &scores, 1000);
+
+    assert!(counts[4] > counts[3] && counts[3] > counts[2] && counts[2] > 0);
+    assert_eq!(counts[5], 0);
+
+    // Hotter personalities stray further from the best column.
+    let hotter = Personality {
+        temperature: 50.0,
+        ..Personality::default()
+    };
+    assert!(count_choices(&hotter, &scores, 1000)[4] < counts[4]);
+}
+
+#[test]
+fn blunders_never_miss_a_win_in_one() {
+    let scores = [
+        (1, Score::win_in(1)),
+        (2, Score::from_heuristic(3)),
+        (3, Score::loss_in(2)),
+    ];
+    let blunderer = Personality {
+        blunder_rate: 1.0,
+        ..Personality::default()
+    };
+    assert_eq!(count_choices(&blunderer, &scores, 50)[1], 50);
+
+    // Unless told to, and then it never plays the best column.
+    let careless = Personality {
+        takes_wins: false,
+        ..blunderer
+    };
+    let counts = count_choices(&careless, &scores, 50);
+    assert_eq!(counts[1], 0);
+    assert!(counts[2] > 0 && counts[3] > 0);
+}
+
+#[test]
+fn personalities_are_given_by_name_or_settings() {
+    let parse =
+        |json: &str| Personality::try_from(serde_json::from_str::<PersonalityInput>(json).unwrap());
+
+    assert_eq!(parse(r#""beginner""#), str_to_personality("beginner"));
+    assert_eq!(
+        parse(r#"{"blunder_rate":0.5}"#),
+        Ok(Personality {
+            blunder_rate: 0.5,
+            ..Personality::default()
+        })
+    );
+    assert_eq!(
+        parse(r#""grandmaster""#).unwrap_err().code(),
+        "unknown_personality"
+    );
+    assert!(parse(r#"{"blunder_rate":1.5}"#).is_err());
+    assert!(parse(r#"{"temperature":-1}"#).is_err());
+}
+
+#[test]
+fn personalities_are_checked_when_made() {
+    assert_eq!(
+        Personality::init(5.0, 0.03, true),
+        str_to_personality("casual")
+    );
+    assert!(Personality::init(0.0, -0.1, true).is_err());
+    assert!(Personality::init(0.0, f64::NAN, true).is_err());
+    assert!(Personality::init(f64::INFINITY, 0.0, true).is_err());
+}
+
+#[test]
+fn personalities_are_checked_however_they_are_deserialized() {
+    let casual = str_to_personality("casual").unwrap();
+    let json = serde_json::to_string(&casual).unwrap();
+    assert_eq!(serde_json::from_str::<Personality>(&json).unwrap(), casual);
+    assert_eq!(
+        serde_json::from_str::<Personality>(r#""beginner""#).ok(),
+        str_to_personality("beginner").ok()
+    );
+
+    assert!(serde_json::from_str::<Personality>(r#"{"blunder_rate":2}"#).is_err());
+    assert!(serde_json::from_str::<Personality>(r#""grandmaster""#).is_err());
+}
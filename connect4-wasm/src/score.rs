@@ -0,0 +1,170 @@
+//! Scores given to moves and positions by the search. A score is either a
+//! proven win or loss, along with how many plies (single moves of either
+//! player) away from the root of the search it is, or a heuristic weight.
+
+use crate::evaluation::{Weight, WIN_WEIGHT};
+use crate::models::board::{COL_NUM, ROW_NUM};
+use std::fmt;
+use std::ops::Neg;
+
+/// Score of a win on the root of the search. Wins further away score one less
+/// per ply, so sooner wins always score more.
+const WIN_SCORE: i32 = 1_000_000;
+
+/// Most plies a win can be away from the root, once the board is full.
+const MAX_PLIES: i32 = (COL_NUM * ROW_NUM) as i32;
+
+/// Score from the point of view of one of the players, higher being better
+/// for them. Any win scores more than any heuristic weight, and any loss less.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Score(i32);
+
+impl Score {
+    /// Bounds of the alpha-beta search window, beyond any real score.
+    pub const MIN: Score = Score(-WIN_SCORE - 1);
+    pub const MAX: Score = Score(WIN_SCORE + 1);
+
+    pub const DRAW: Score = Score(0);
+
+    pub fn win_in(plies: u8) -> Score {
+        Score(WIN_SCORE - plies as i32)
+    }
+
+    pub fn loss_in(plies: u8) -> Score {
+        -Score::win_in(plies)
+    }
+
+    /// Heuristic weights are kept below the weight of a win, so they can't be
+    /// mistaken for a proven result.
+    pub fn from_heuristic(weight: Weight) -> Score {
+        Score(weight.clamp(-WIN_WEIGHT + 1, WIN_WEIGHT - 1) as i32)
+    }
+
+    pub fn is_win(&self) -> bool {
+        self.0 >= WIN_SCORE - MAX_PLIES
+    }
+
+    pub fn is_loss(&self) -> bool {
+        self.0 <= -WIN_SCORE + MAX_PLIES
+    }
+
+    /// Tells if the score is a win or a loss found by the search, rather than
+    /// a guess of the heuristic evaluation.
+    pub fn is_proven(&self) -> bool {
+        self.is_win() || self.is_loss()
+    }
+
+    /// Plies from the root to the end of the game, for proven results.
+    pub fn get_plies(&self) -> Option<u8> {
+        if self.is_proven() {
+            Option::Some((WIN_SCORE - self.0.abs()) as u8)
+        } else {
+            Option::None
+        }
+    }
+
+    /// Heuristic weight, for scores which aren't proven.
+    pub fn get_heuristic(&self) -> Option<Weight> {
+        if self.is_proven() {
+            Option::None
+        } else {
+            Option::Some(self.0 as Weight)
+        }
+    }
+
+    /// Closest score below this one, used to keep ties exact in the search.
+    pub fn just_below(self) -> Score {
+        Score(self.0 - 1).max(Score::MIN)
+    }
+
+    /// Score as it's kept in the transposition table, with wins and losses
+    /// counted from the position at `ply` instead of the root, as the same
+    /// position can be reached at different plies in later searches.
+    pub fn to_table(self, ply: u8) -> Score {
+        self.shift_plies(-(ply as i32))
+    }
+
+    /// Reverses `to_table` for the position at `ply` in the current search.
+    pub fn to_root(self, ply: u8) -> Score {
+        self.shift_plies(ply as i32)
+    }
+
+    fn shift_plies(self, plies: i32) -> Score {
+        if self.is_win() {
+            Score(self.0 - plies)
+        } else if self.is_loss() {
+            Score(self.0 + plies)
+        } else {
+            self
+        }
+    }
+}
+
+impl Neg for Score {
+    type Output = Score;
+
+    fn neg(self) -> Score {
+        Score(-self.0)
+    }
+}
+
+/// Proven results read as "win in 5" or "loss in 2", and heuristic weights as
+/// plain numbers.
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.get_plies() {
+            Option::Some(plies) if self.is_win() => write!(f, "win in {}", plies),
+            Option::Some(plies) => write!(f, "loss in {}", plies),
+            Option::None => write!(f, "{:+}", self.0),
+        }
+    }
+}
+
+// Module tests!!
+
+#[test]
+fn sooner_wins_score_more() {
+    assert!(Score::win_in(1) > Score::win_in(3));
+    assert!(Score::loss_in(4) > Score::loss_in(2));
+    assert!(Score::win_in(42) > Score::from_heuristic(Weight::MAX));
+    assert!(Score::loss_in(42) < Score::from_heuristic(Weight::MIN));
+    assert!(Score::MIN < Score::loss_in(0) && Score::win_in(0) < Score::MAX);
+    assert_eq!(-Score::MAX, Score::MIN);
+}
+
+#[test]
+fn proven_results_are_told_apart() {
+    assert_eq!(Score::win_in(5).get_plies(), Option::Some(5));
+    assert_eq!(Score::loss_in(2).get_plies(), Option::Some(2));
+    assert!(Score::loss_in(2).is_loss() && !Score::loss_in(2).is_win());
+
+    let heuristic = Score::from_heuristic(-12);
+    assert!(!heuristic.is_proven());
+    assert_eq!(heuristic.get_plies(), Option::None);
+    assert_eq!(heuristic.get_heuristic(), Option::Some(-12));
+    assert!(!Score::DRAW.is_proven());
+}
+
+#[test]
+fn table_scores_are_relative_to_the_position() {
+    // Win 5 plies from the root, found in a position 3 plies in, is 2 plies
+    // away from that position, and 3 plies away when it's reached on ply 1.
+    let stored = Score::win_in(5).to_table(3);
+    assert_eq!(stored, Score::win_in(2));
+    assert_eq!(stored.to_root(1), Score::win_in(3));
+
+    assert_eq!(Score::loss_in(6).to_table(2), Score::loss_in(4));
+    assert_eq!(
+        Score::from_heuristic(7).to_table(4),
+        Score::from_heuristic(7)
+    );
+}
+
+#[test]
+fn scores_read_as_wins_losses_or_weights() {
+    assert_eq!(Score::win_in(5).to_string(), "win in 5");
+    assert_eq!(Score::loss_in(2).to_string(), "loss in 2");
+    assert_eq!(Score::from_heuristic(12).to_string(), "+12");
+    assert_eq!(Score::from_heuristic(-3).to_string(), "-3");
+    assert_eq!(Score::DRAW.to_string(), "+0");
+}
@@ -0,0 +1,669 @@
+//! Game session kept alive on the Rust side between calls from JS. Unlike the
+//! stateless JSON entry points, it remembers the moves played, so they can be
+//! undone, and keeps the engine of the AI, with its search cache, between its
+//! moves.
+
+use crate::analysis::{analyze, solve, ColumnAnalysis};
+use crate::connect4::Connect4;
+use crate::control::{CancelToken, Progress};
+use crate::engine::{engine_for_difficulty, Engine, EngineRegistry};
+use crate::enums::difficulty::{str_to_difficulty, Difficulty, DifficultyInput};
+use crate::enums::game_outcome::{is_game_over, GameOutcome};
+use crate::enums::player::{player_to_json_str, Player};
+use crate::error::Connect4Error;
+use crate::models::board::{Board, Cell};
+use crate::personality::{str_to_personality, Personality, PersonalityInput};
+use crate::transposition::TranspositionTable;
+use crate::validation::validate_board;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use wasm_bindgen::prelude::*;
+
+/// Outcome of the game, as reported back to the UI. When someone has won,
+/// `lines` holds the cells of every line they've won with.
+#[derive(Serialize, Deserialize)]
+pub struct Outcome {
+    pub status: String,
+    pub winner: Option<String>,
+    pub lines: Vec<Vec<Cell>>,
+}
+
+pub fn outcome_to_output(game: &Connect4) -> Outcome {
+    let (status, winner, lines) = match game.get_outcome() {
+        GameOutcome::InProgress => ("in_progress", Option::None, vec![]),
+        GameOutcome::Won(player, _) => (
+            "won",
+            Option::Some(player_to_json_str(&player)),
+            game.get_winning_lines(&player),
+        ),
+        GameOutcome::Draw => ("draw", Option::None, vec![]),
+    };
+
+    Outcome {
+        status: String::from(status),
+        winner: winner.map(String::from),
+        lines,
+    }
+}
+
+/// Fails if the AI is given a personality the engine can't play with.
+fn check_personality(
+    engine: &dyn Engine,
+    game: &Connect4,
+    personality: Option<Personality>,
+) -> Result<(), Connect4Error> {
+    if personality.is_some() && !engine.uses_personality(game) {
+        Err(Connect4Error::UnsupportedPersonality {
+            engine: String::from(engine.get_name()),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// Everything needed to restore a session: the board it started from, and the
+/// columns (counted from one) played since.
+#[derive(Serialize, Deserialize)]
+struct SessionState {
+    difficulty: Difficulty,
+    start: Vec<Vec<String>>,
+    moves: Vec<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    /// Engine selected for the AI, if it's not the one of the difficulty.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    engine: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    personality: Option<Personality>,
+}
+
+/// Game between the player and the AI. Moves are played for whoever's turn it
+/// is, so it's up to the caller to decide when the AI moves.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct GameSession {
+    start: Connect4,
+    game: Connect4,
+    moves: Vec<u8>,
+    engine: Box<dyn Engine>,
+    engine_input: Option<serde_json::Value>,
+}
+
+impl GameSession {
+    /// Session starting from an empty board, with the player moving first.
+    pub fn init(difficulty: Difficulty) -> GameSession {
+        GameSession::init_from_game(Connect4::init_vs_ai(difficulty))
+    }
+
+    /// Session starting from a board from the input, which has to be valid
+    /// with `to_move` being next to play.
+    pub fn init_from_board(
+        difficulty: Difficulty,
+        str_board: Vec<Vec<String>>,
+        to_move: Player,
+    ) -> Result<GameSession, Connect4Error> {
+        let diagnostics = validate_board(&str_board, &to_move);
+        if !diagnostics.is_empty() {
+            return Err(Connect4Error::from_diagnostics(diagnostics));
+        }
+
+        let next = if to_move == Player::One {
+            Player::AI
+        } else {
+            Player::One
+        };
+        Ok(GameSession::init_from_game(Connect4::init_from_input_for(
+            to_move, next, difficulty, str_board,
+        )))
+    }
+
+    /// Restores a session serialized with `to_json_string`.
+    pub fn init_from_json(json: &str) -> Result<GameSession, Connect4Error> {
+        let state = serde_json::from_str::<SessionState>(json)?;
+        let to_move = Board::init_from(&state.start).get_side_to_move();
+        let mut session = GameSession::init_from_board(state.difficulty, state.start, to_move)?;
+        session.set_seed(state.seed);
+        session.set_personality(state.personality)?;
+        if let Option::Some(engine) = state.engine {
+            session.select_engine(&EngineRegistry::init(), engine)?;
+        }
+
+        state
+            .moves
+            .iter()
+            .try_for_each(|col| session.play_col(*col).map(|_| ()))?;
+        Ok(session)
+    }
+
+    fn init_from_game(game: Connect4) -> GameSession {
+        GameSession {
+            engine: engine_for_difficulty(game.difficulty),
+            start: game.clone(),
+            game,
+            moves: vec![],
+            engine_input: Option::None,
+        }
+    }
+
+    /// Lets the AI play with the engine selected by the input, either its name
+    /// or its settings, instead of the one of the difficulty. Engines which
+    /// can't play with the personality of the AI are refused.
+    pub fn select_engine(
+        &mut self,
+        registry: &EngineRegistry,
+        input: serde_json::Value,
+    ) -> Result<(), Connect4Error> {
+        let engine = registry.create(&input)?;
+        check_personality(engine.as_ref(), &self.game, self.game.personality)?;
+        self.engine = engine;
+        self.engine_input = Option::Some(input);
+        Ok(())
+    }
+
+    pub fn get_engine(&self) -> &dyn Engine {
+        self.engine.as_ref()
+    }
+
+    /// Seeds the AI, so it plays the same moves every time in the same
+    /// positions. Without a seed it picks randomly between equally good moves.
+    pub fn set_seed(&mut self, seed: Option<u64>) {
+        self.start.seed = seed;
+        self.game.seed = seed;
+    }
+
+    /// Lets the AI pick between the columns it scored like a person would,
+    /// instead of always playing the best one. Refused if the engine settles
+    /// on a single column.
+    pub fn set_personality(
+        &mut self,
+        personality: Option<Personality>,
+    ) -> Result<(), Connect4Error> {
+        check_personality(self.engine.as_ref(), &self.game, personality)?;
+        self.start.personality = personality;
+        self.game.personality = personality;
+        Ok(())
+    }
+
+    pub fn get_game(&self) -> &Connect4 {
+        &self.game
+    }
+
+    pub fn get_moves(&self) -> &[u8] {
+        &self.moves
+    }
+
+    pub fn get_outcome(&self) -> GameOutcome {
+        self.game.get_outcome()
+    }
+
+    /// Plays the column for whoever's turn it is.
+    pub fn play_col(&mut self, col_num: u8) -> Result<GameOutcome, Connect4Error> {
+        self.game.drop_token_in_col(col_num)?;
+        self.game.switch_players();
+        self.moves.push(col_num);
+        Ok(self.game.get_outcome())
+    }
+
+    /// Lets the AI pick and play a column for whoever's turn it is.
+    pub fn play_ai(&mut self) -> Result<(u8, GameOutcome), Connect4Error> {
+        if is_game_over(&self.game.get_outcome()) {
+            return Err(Connect4Error::GameOver);
+        }
+
+        let played = self.game.get_engine_move(self.engine.as_mut());
+        self.after_ai_move(played)
+    }
+
+    /// Same as `play_ai`, but the search can be cancelled with the token, in
+    /// which case the AI plays the best column it found so far, and it reports
+    /// its progress as it goes.
+    pub fn play_ai_controlled(
+        &mut self,
+        cancel: Option<&CancelToken>,
+        on_progress: &mut dyn FnMut(&Progress),
+    ) -> Result<(u8, GameOutcome), Connect4Error> {
+        if is_game_over(&self.game.get_outcome()) {
+            return Err(Connect4Error::GameOver);
+        }
+
+        let played =
+            self.game
+                .get_engine_move_controlled(self.engine.as_mut(), cancel, on_progress);
+        self.after_ai_move(played)
+    }
+
+    fn after_ai_move(
+        &mut self,
+        played: (Option<u8>, GameOutcome),
+    ) -> Result<(u8, GameOutcome), Connect4Error> {
+        match played {
+            (Option::Some(col_num), outcome) => {
+                self.game.switch_players();
+                self.moves.push(col_num);
+                Ok((col_num, outcome))
+            }
+            (Option::None, _) => Err(Connect4Error::GameOver),
+        }
+    }
+
+    /// Scores every column the side to move could play, for hints. Engines
+    /// which can't score the columns leave it to minimax.
+    pub fn get_analysis(&mut self) -> Result<Vec<ColumnAnalysis>, Connect4Error> {
+        if is_game_over(&self.game.get_outcome()) {
+            return Err(Connect4Error::GameOver);
+        }
+
+        Ok(self
+            .engine
+            .analyze(&self.game)
+            .unwrap_or_else(|| analyze(&self.game, &mut TranspositionTable::init_default())))
+    }
+
+    /// Lets the engine think while it's the player's turn, until it's done or
+    /// the token is cancelled.
+    pub fn ponder(&mut self, cancel: Option<&CancelToken>) -> Result<(), Connect4Error> {
+        if is_game_over(&self.game.get_outcome()) {
+            return Err(Connect4Error::GameOver);
+        }
+
+        self.engine.ponder(&self.game, cancel);
+        Ok(())
+    }
+
+    /// Exact value of every column the side to move could play, with both
+    /// players playing perfectly.
+    pub fn get_solution(&self) -> Result<Vec<ColumnAnalysis>, Connect4Error> {
+        if is_game_over(&self.game.get_outcome()) {
+            return Err(Connect4Error::GameOver);
+        }
+
+        Ok(solve(&self.game))
+    }
+
+    /// Takes back the last move, returning the column it was played in. The
+    /// game is replayed from the start without it.
+    pub fn undo_move(&mut self) -> Option<u8> {
+        let col_num = self.moves.pop()?;
+
+        self.game = self.start.clone();
+        self.moves.iter().for_each(|col| {
+            // Columns were all played before, so they're still legal.
+            let _ = self.game.drop_token_in_col(*col);
+            self.game.switch_players();
+        });
+        Option::Some(col_num)
+    }
+
+    pub fn to_json_string(&self) -> String {
+        let state = SessionState {
+            difficulty: self.game.difficulty,
+            start: self.start.get_board().get_input_board(),
+            moves: self.moves.clone(),
+            seed: self.game.seed,
+            engine: self.engine_input.clone(),
+            personality: self.game.personality,
+        };
+        serde_json::to_string(&state).unwrap_or_default()
+    }
+}
+
+/// Difficulty given from JS, either as JSON, or just as its name.
+fn parse_difficulty(text: &str) -> Result<Difficulty, Connect4Error> {
+    match serde_json::from_str::<DifficultyInput>(text) {
+        Ok(input) => Difficulty::try_from(input),
+        Err(_) => str_to_difficulty(text),
+    }
+}
+
+/// Personality given from JS, either as JSON, or just as its name.
+fn parse_personality(text: &str) -> Result<Personality, Connect4Error> {
+    match serde_json::from_str::<PersonalityInput>(text) {
+        Ok(input) => Personality::try_from(input),
+        Err(_) => str_to_personality(text),
+    }
+}
+
+/// Engine given from JS, either as JSON, or just as its name.
+fn parse_engine(text: &str) -> serde_json::Value {
+    serde_json::from_str::<serde_json::Value>(text)
+        .unwrap_or_else(|_| serde_json::Value::String(String::from(text)))
+}
+
+fn to_js_error(err: Connect4Error) -> JsValue {
+    JsValue::from_str(err.to_json().as_str())
+}
+
+fn outcome_to_json(game: &Connect4) -> String {
+    serde_json::to_string(&outcome_to_output(game)).unwrap_or_default()
+}
+
+/// API of the session exposed to JS. Errors are thrown as their JSON.
+#[wasm_bindgen]
+impl GameSession {
+    #[wasm_bindgen(constructor)]
+    pub fn new(difficulty: &str) -> Result<GameSession, JsValue> {
+        parse_difficulty(difficulty)
+            .map(GameSession::init)
+            .map_err(to_js_error)
+    }
+
+    #[wasm_bindgen(js_name = fromJson)]
+    pub fn from_json(json: &str) -> Result<GameSession, JsValue> {
+        GameSession::init_from_json(json).map_err(to_js_error)
+    }
+
+    /// Plays the column, counted from one, and returns the outcome as JSON.
+    pub fn play(&mut self, col_num: u8) -> Result<String, JsValue> {
+        self.play_col(col_num)
+            .map(|_| outcome_to_json(&self.game))
+            .map_err(to_js_error)
+    }
+
+    /// Lets the AI play, and returns the column it played, counted from one.
+    #[wasm_bindgen(js_name = aiMove)]
+    pub fn ai_move(&mut self) -> Result<u8, JsValue> {
+        self.play_ai()
+            .map(|(col_num, _)| col_num)
+            .map_err(to_js_error)
+    }
+
+    /// Same as `aiMove`, but calls `on_progress` with the progress of the
+    /// search as JSON after every depth. The callback can cancel the token to
+    /// stop the search early.
+    #[wasm_bindgen(js_name = aiMoveWithProgress)]
+    pub fn ai_move_with_progress(
+        &mut self,
+        cancel: &CancelToken,
+        on_progress: &js_sys::Function,
+    ) -> Result<u8, JsValue> {
+        let mut report = |progress: &Progress| {
+            if let Ok(json) = serde_json::to_string(progress) {
+                let _ = on_progress.call1(&JsValue::NULL, &JsValue::from_str(json.as_str()));
+            }
+        };
+
+        self.play_ai_controlled(Option::Some(cancel), &mut report)
+            .map(|(col_num, _)| col_num)
+            .map_err(to_js_error)
+    }
+
+    /// Scores of every column the side to move could play, as JSON.
+    pub fn analyze(&mut self) -> Result<String, JsValue> {
+        self.get_analysis()
+            .map(|analysis| serde_json::to_string(&analysis).unwrap_or_default())
+            .map_err(to_js_error)
+    }
+
+    /// Exact values of every column the side to move could play, as JSON,
+    /// the same way as `analyze`. Can take a long time early in the game.
+    #[wasm_bindgen(js_name = solve)]
+    pub fn solve_js(&self) -> Result<String, JsValue> {
+        self.get_solution()
+            .map(|analysis| serde_json::to_string(&analysis).unwrap_or_default())
+            .map_err(to_js_error)
+    }
+
+    /// Lets the AI play with the engine, given by name, like `greedy`, or as
+    /// JSON with its settings, like `{"mcts":{"iterations":5000}}`.
+    #[wasm_bindgen(js_name = setEngine)]
+    pub fn set_engine(&mut self, engine: &str) -> Result<(), JsValue> {
+        self.select_engine(&EngineRegistry::init(), parse_engine(engine))
+            .map_err(to_js_error)
+    }
+
+    /// Lets the AI think while it's the player's turn. Returns once it's done,
+    /// or the token is cancelled.
+    #[wasm_bindgen(js_name = ponder)]
+    pub fn ponder_js(&mut self, cancel: &CancelToken) -> Result<(), JsValue> {
+        self.ponder(Option::Some(cancel)).map_err(to_js_error)
+    }
+
+    /// Lets the AI play like a person, given by the name of a preset, like
+    /// `beginner`, or as JSON with its settings, like
+    /// `{"temperature":10,"blunder_rate":0.1}`.
+    #[wasm_bindgen(js_name = setPersonality)]
+    pub fn set_personality_js(&mut self, personality: &str) -> Result<(), JsValue> {
+        parse_personality(personality)
+            .and_then(|personality| self.set_personality(Option::Some(personality)))
+            .map_err(to_js_error)
+    }
+
+    #[wasm_bindgen(js_name = setSeed)]
+    pub fn seed_with(&mut self, seed: u32) {
+        self.set_seed(Option::Some(seed as u64));
+    }
+
+    pub fn undo(&mut self) -> Option<u8> {
+        self.undo_move()
+    }
+
+    /// Outcome of the game so far, as JSON.
+    pub fn outcome(&self) -> String {
+        outcome_to_json(&self.game)
+    }
+
+    #[wasm_bindgen(js_name = toJson)]
+    pub fn to_json(&self) -> String {
+        self.to_json_string()
+    }
+}
+
+// Module tests!!
+
+#[test]
+fn session_plays_moves_for_both_sides() {
+    let mut session = GameSession::init(Difficulty::Test);
+
+    assert_eq!(session.play_col(4), Ok(GameOutcome::InProgress));
+    assert_eq!(session.get_game().get_current_player(), Player::AI);
+
+    let (col_num, _) = session.play_ai().unwrap();
+    assert_eq!(session.get_moves(), &[4, col_num]);
+    assert_eq!(session.get_game().get_current_player(), Player::One);
+}
+
+#[test]
+fn session_can_undo_moves() {
+    let mut session = GameSession::init(Difficulty::Test);
+    [1, 2, 1, 2, 1, 2].iter().for_each(|col| {
+        session.play_col(*col).unwrap();
+    });
+    assert_eq!(
+        session.play_col(1),
+        Ok(GameOutcome::Won(Player::One, 0b1111))
+    );
+    assert_eq!(session.play_col(3), Err(Connect4Error::GameOver));
+    assert_eq!(session.play_ai(), Err(Connect4Error::GameOver));
+
+    assert_eq!(session.undo_move(), Option::Some(1));
+    assert_eq!(session.get_outcome(), GameOutcome::InProgress);
+    assert_eq!(session.get_game().get_current_player(), Player::One);
+    assert_eq!(session.get_moves(), &[1, 2, 1, 2, 1, 2]);
+
+    (0..6).for_each(|_| {
+        session.undo_move();
+    });
+    assert_eq!(session.undo_move(), Option::None);
+    assert_eq!(session.get_game().get_board().count_moves_left(), 42);
+}
+
+#[test]
+fn session_survives_a_round_trip_through_json() {
+    let board = vec![
+        vec![String::from("player")],
+        vec![],
+        vec![],
+        vec![],
+        vec![],
+        vec![],
+        vec![],
+    ];
+    let mut session = GameSession::init_from_board(Difficulty::Hard, board, Player::AI).unwrap();
+    session.play_col(4).unwrap();
+    session.play_col(4).unwrap();
+
+    let restored = GameSession::init_from_json(&session.to_json_string()).unwrap();
+    assert_eq!(restored.to_json_string(), session.to_json_string());
+    assert_eq!(restored.get_game().get_key(), session.get_game().get_key());
+    assert_eq!(restored.get_game().difficulty, Difficulty::Hard);
+
+    assert!(GameSession::init_from_json(r#"{"difficulty":"hard"}"#).is_err());
+}
+
+#[test]
+fn difficulty_can_be_given_by_name_or_as_json() {
+    assert_eq!(parse_difficulty("hard"), Ok(Difficulty::Hard));
+    assert_eq!(parse_difficulty(r#""no_chance""#), Ok(Difficulty::NoChance));
+    assert_eq!(
+        parse_difficulty(r#"{"time_ms":300}"#),
+        Ok(Difficulty::TimeBudget(300.0))
+    );
+    assert!(parse_difficulty("harder").is_err());
+}
+
+#[test]
+fn session_reports_progress_of_the_ai() {
+    let mut session = GameSession::init(Difficulty::Normal);
+    session.play_col(4).unwrap();
+
+    let mut reported = vec![];
+    let (col_num, _) = session
+        .play_ai_controlled(Option::None, &mut |progress| reported.push(*progress))
+        .unwrap();
+
+    assert_eq!(
+        reported.iter().map(|p| p.depth).collect::<Vec<u8>>(),
+        vec![0, 1, 2, 3]
+    );
+    assert_eq!(reported.last().unwrap().best_col, Option::Some(col_num));
+    assert_eq!(session.get_moves(), &[4, col_num]);
+}
+
+#[test]
+fn seeded_sessions_replay_the_same_game() {
+    let play_game = |seed: u64| {
+        let mut session = GameSession::init(Difficulty::Test);
+        session.set_seed(Option::Some(seed));
+        [4, 3, 5, 1].iter().for_each(|col| {
+            session.play_col(*col).unwrap();
+            session.play_ai().unwrap();
+        });
+        session
+    };
+
+    let session = play_game(9);
+    assert_eq!(play_game(9).get_moves(), session.get_moves());
+
+    let restored = GameSession::init_from_json(&session.to_json_string()).unwrap();
+    assert_eq!(restored.get_game().seed, Option::Some(9));
+}
+
+#[test]
+fn session_plays_with_the_selected_engine() {
+    let mut session = GameSession::init(Difficulty::Hard);
+    assert_eq!(session.get_engine().get_name(), "minimax");
+
+    session
+        .select_engine(&EngineRegistry::init(), parse_engine("greedy"))
+        .unwrap();
+    [1, 1, 1].iter().for_each(|col| {
+        session.play_col(*col).unwrap();
+        session.play_ai().unwrap();
+    });
+    assert_eq!(session.get_engine().get_name(), "greedy");
+    assert_eq!(session.get_analysis().unwrap().len(), 7);
+
+    let restored = GameSession::init_from_json(&session.to_json_string()).unwrap();
+    assert_eq!(restored.get_engine().get_name(), "greedy");
+    assert!(session.to_json_string().contains(r#""engine":"greedy""#));
+
+    assert_eq!(
+        session.select_engine(&EngineRegistry::init(), parse_engine("deep_blue")),
+        Err(Connect4Error::UnknownEngine {
+            name: String::from("deep_blue")
+        })
+    );
+    assert_eq!(session.get_engine().get_name(), "greedy");
+}
+
+#[test]
+fn session_ponders_on_the_players_turn() {
+    let mut session = GameSession::init(Difficulty::Normal);
+    session
+        .select_engine(
+            &EngineRegistry::init(),
+            parse_engine(r#"{"mcts":{"iterations":50}}"#),
+        )
+        .unwrap();
+    assert_eq!(session.ponder(Option::None), Ok(()));
+    assert_eq!(session.get_engine().get_name(), "mcts");
+
+    [1, 2, 1, 2, 1, 2, 1].iter().for_each(|col| {
+        session.play_col(*col).unwrap();
+    });
+    assert_eq!(session.ponder(Option::None), Err(Connect4Error::GameOver));
+}
+
+#[test]
+fn session_keeps_the_personality_of_the_ai() {
+    let mut session = GameSession::init(Difficulty::Easy);
+    session
+        .set_personality(Option::Some(parse_personality("beginner").unwrap()))
+        .unwrap();
+    session.set_seed(Option::Some(4));
+    [4, 4, 3].iter().for_each(|col| {
+        session.play_col(*col).unwrap();
+        session.play_ai().unwrap();
+    });
+
+    let restored = GameSession::init_from_json(&session.to_json_string()).unwrap();
+    assert_eq!(restored.get_moves(), session.get_moves());
+    assert_eq!(
+        restored.get_game().personality,
+        str_to_personality("beginner").ok()
+    );
+
+    assert_eq!(
+        parse_personality(r#"{"temperature":3}"#).map(|p| p.get_temperature()),
+        Ok(3.0)
+    );
+    assert!(parse_personality("grumpy").is_err());
+
+    let json = session
+        .to_json_string()
+        .replace(r#""blunder_rate":0.1"#, r#""blunder_rate":2"#);
+    assert!(json.contains(r#""blunder_rate":2"#));
+    assert!(GameSession::init_from_json(&json).is_err());
+}
+
+#[test]
+fn personalities_need_an_engine_scoring_every_column() {
+    let beginner = str_to_personality("beginner").ok();
+    let unsupported = |engine: &str| {
+        Err(Connect4Error::UnsupportedPersonality {
+            engine: String::from(engine),
+        })
+    };
+
+    let mut session = GameSession::init(str_to_difficulty("mcts").unwrap());
+    assert_eq!(session.set_personality(beginner), unsupported("mcts"));
+    assert_eq!(session.get_game().personality, Option::None);
+
+    // Minimax on the MCTS difficulty only comes up with the column of MCTS.
+    session
+        .select_engine(&EngineRegistry::init(), parse_engine(r#""minimax""#))
+        .unwrap();
+    assert_eq!(session.set_personality(beginner), unsupported("minimax"));
+
+    let mut session = GameSession::init(Difficulty::Easy);
+    session.set_personality(beginner).unwrap();
+    assert_eq!(
+        session.select_engine(&EngineRegistry::init(), parse_engine(r#""random""#)),
+        unsupported("random")
+    );
+    assert_eq!(session.get_engine().get_name(), "minimax");
+    session
+        .select_engine(&EngineRegistry::init(), parse_engine(r#""greedy""#))
+        .unwrap();
+    assert!(session.play_ai().is_ok());
+}
@@ -0,0 +1,546 @@
+//! Solver finding the exact result of a position, with both players playing
+//! perfectly from there on. Unlike the minimax search it has no depth limit,
+//! and no heuristics, it searches until the end of the game on every line.
+//!
+//! It's a negamax search with alpha-beta pruning on a compact bitboard, which
+//! only ever looks at moves that don't lose right away. Columns in the centre
+//! and moves creating the most threats are searched first, and upper bounds
+//! are cached in a transposition table. The exact value is then narrowed down
+//! with a series of null-window searches, which prune a lot more than a single
+//! search with the full window.
+
+use crate::connect4::Connect4;
+use crate::enums::game_outcome::GameOutcome;
+use crate::evaluation::{column_mask, winning_cells};
+use crate::models::board::{mirror_bitboard, Bitboard, BOARD_MASK, BOTTOM_MASK, COL_NUM, ROW_NUM};
+use crate::score::Score;
+#[cfg(test)]
+use crate::test_support::game_after;
+use std::cmp::Ordering;
+
+/// Number of cells on the board.
+const CELLS: i32 = (COL_NUM * ROW_NUM) as i32;
+
+/// Columns in the order they're searched. Columns in the centre are part of
+/// more lines, so they're more often the best moves.
+const COLUMN_ORDER: [usize; COL_NUM as usize] = [3, 2, 4, 1, 5, 0, 6];
+
+/// Default number of entries in the table. It's a prime, so the slot along
+/// with the lower half of the key kept in it tell the position apart exactly.
+pub const DEFAULT_SOLVER_TABLE_SIZE: usize = 1_048_583;
+
+/// Smallest table telling positions apart exactly. Keys take 49 bits, and the
+/// table keeps the lower 32 of them, so the slot has to tell apart the other
+/// 17, with a size that's odd, and so shares no factor with 2^32.
+const MIN_SOLVER_TABLE_SIZE: usize = 1 << 17;
+
+/// Position as the solver sees it, from the point of view of the side to
+/// move.
+#[derive(Debug, Clone, Copy)]
+struct Position {
+    current: Bitboard, // Tokens of the side to move
+    mask: Bitboard,    // Tokens of both players
+    moves: i32,        // Tokens on the board
+}
+
+impl Position {
+    fn init_from(game: &Connect4) -> Position {
+        let board = game.get_board();
+        let mask = board.get_played_mask();
+        Position {
+            current: board.get_tokens_for_player(&game.get_current_player()),
+            mask,
+            moves: mask.count_ones() as i32,
+        }
+    }
+
+    /// Plays the move, a bitboard with just the cell to play set.
+    fn play(&mut self, cell: Bitboard) {
+        self.current ^= self.mask;
+        self.mask |= cell;
+        self.moves += 1;
+    }
+
+    /// Unique for every position, as long as the side to move is known, and
+    /// shared with its mirror image, which has the same value. Like the key
+    /// of the board, the sum never carries over between columns, so it can
+    /// be mirrored as it is.
+    fn key(&self) -> u64 {
+        let key = self.current + self.mask;
+        key.min(mirror_bitboard(key))
+    }
+
+    /// Lowest empty cell of every column that isn't full.
+    fn playable(&self) -> Bitboard {
+        (self.mask + BOTTOM_MASK) & BOARD_MASK
+    }
+
+    fn is_winning_move(&self, cell: Bitboard) -> bool {
+        winning_cells(self.current, self.mask) & cell != 0
+    }
+
+    fn can_win_next(&self) -> bool {
+        self.is_winning_move(self.playable())
+    }
+
+    /// Moves which don't let the opponent win on their next move. When the
+    /// opponent threatens to win somewhere, that's the only move left, and if
+    /// they threaten to win in two places, there's none. Assumes the side to
+    /// move can't win right away.
+    fn non_losing_moves(&self) -> Bitboard {
+        let playable = self.playable();
+        let opponent_wins = winning_cells(self.current ^ self.mask, self.mask);
+        let forced = playable & opponent_wins;
+
+        let playable = match forced.count_ones() {
+            0 => playable,
+            1 => forced,
+            _ => return 0,
+        };
+        // Playing right below a cell the opponent needs lets them take it.
+        playable & !(opponent_wins >> 1)
+    }
+
+    /// Number of cells the side to move could win with after the move.
+    fn count_threats_after(&self, cell: Bitboard) -> u32 {
+        winning_cells(self.current | cell, self.mask | cell).count_ones()
+    }
+
+    /// Non-losing moves, ordered by the threats they create, and then from the
+    /// centre out.
+    fn ordered_moves(&self, moves: Bitboard) -> ([Bitboard; COL_NUM as usize], usize) {
+        let mut ordered = [(0, 0); COL_NUM as usize];
+        let mut len = 0;
+
+        COLUMN_ORDER.iter().for_each(|col| {
+            let cell = moves & column_mask(*col);
+            if cell == 0 {
+                return;
+            }
+
+            // Insertion sort, keeping moves with as many threats in order.
+            let threats = self.count_threats_after(cell);
+            let mut idx = len;
+            while idx > 0 && ordered[idx - 1].1 < threats {
+                ordered[idx] = ordered[idx - 1];
+                idx -= 1;
+            }
+            ordered[idx] = (cell, threats);
+            len += 1;
+        });
+
+        (ordered.map(|(cell, _)| cell), len)
+    }
+}
+
+/// Cache of upper bounds of values of positions searched before.
+#[derive(Debug, Clone)]
+struct SolverTable {
+    keys: Vec<u32>,
+    values: Vec<Option<i8>>,
+}
+
+impl SolverTable {
+    fn init(size: usize) -> SolverTable {
+        SolverTable {
+            keys: vec![0; size.max(1)],
+            values: vec![Option::None; size.max(1)],
+        }
+    }
+
+    fn get(&self, key: u64) -> Option<i32> {
+        let slot = self.slot(key);
+        self.values[slot]
+            .filter(|_| self.keys[slot] == key as u32)
+            .map(|value| value as i32)
+    }
+
+    fn store(&mut self, key: u64, value: i32) {
+        let slot = self.slot(key);
+        self.keys[slot] = key as u32;
+        self.values[slot] = Option::Some(value as i8);
+    }
+
+    fn slot(&self, key: u64) -> usize {
+        (key % self.keys.len() as u64) as usize
+    }
+}
+
+/// Solves positions, keeping its table between them, so positions following
+/// each other are solved faster.
+///
+/// Values inside the solver tell how many of their own tokens the winner has
+/// left to play after winning, so sooner wins are worth more. They're positive
+/// when the side to move wins, negative when it loses, and zero for a draw.
+#[derive(Debug, Clone)]
+pub struct Solver {
+    table: SolverTable,
+    nodes: u64,
+    node_limit: Option<u64>,
+    started_at: u64, // Nodes searched before the current solve
+    aborted: bool,
+}
+
+impl Default for Solver {
+    fn default() -> Solver {
+        Solver::init()
+    }
+}
+
+impl Solver {
+    pub fn init() -> Solver {
+        Solver::init_with_size(DEFAULT_SOLVER_TABLE_SIZE)
+    }
+
+    /// Solver with a table of the given size, which has to be odd, and at
+    /// least `MIN_SOLVER_TABLE_SIZE`, or positions sharing an entry could be
+    /// mistaken for each other.
+    pub fn init_with_size(table_size: usize) -> Solver {
+        assert!(
+            table_size >= MIN_SOLVER_TABLE_SIZE && table_size % 2 == 1,
+            "solver table of {} entries can't tell positions apart",
+            table_size
+        );
+        Solver {
+            table: SolverTable::init(table_size),
+            nodes: 0,
+            node_limit: Option::None,
+            started_at: 0,
+            aborted: false,
+        }
+    }
+
+    /// Solver gives up on positions it can't solve searching at most so many
+    /// positions, instead of searching for as long as it takes.
+    pub fn with_node_limit(mut self, limit: u64) -> Solver {
+        self.node_limit = Option::Some(limit);
+        self
+    }
+
+    /// Tells if the solver gave up on the last position, in which case what
+    /// it returned doesn't mean anything.
+    pub fn is_aborted(&self) -> bool {
+        self.aborted
+    }
+
+    /// Positions searched so far, over all the solves.
+    pub fn get_nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// Exact score of the position for the side to move. Wins and losses are
+    /// counted in plies from the position.
+    pub fn solve(&mut self, game: &Connect4) -> Score {
+        match game.get_outcome() {
+            GameOutcome::Won(player, _) if player == game.get_current_player() => Score::win_in(0),
+            GameOutcome::Won(_, _) => Score::loss_in(0),
+            GameOutcome::Draw => Score::DRAW,
+            GameOutcome::InProgress => {
+                let position = Position::init_from(game);
+                self.start();
+                value_to_score(self.solve_position(&position), position.moves)
+            }
+        }
+    }
+
+    /// Exact scores of all playable columns for the side to move, the same way
+    /// the minimax search scores them.
+    pub fn get_column_scores(&mut self, game: &Connect4) -> Vec<(u8, Score)> {
+        if game.get_outcome() != GameOutcome::InProgress {
+            return vec![];
+        }
+
+        let position = Position::init_from(game);
+        let playable = position.playable();
+        self.start();
+
+        (0..COL_NUM as usize)
+            .filter_map(|col| {
+                let cell = playable & column_mask(col);
+                if cell == 0 {
+                    return Option::None;
+                }
+
+                let score = if position.is_winning_move(cell) {
+                    Score::win_in(1)
+                } else {
+                    let mut next = position;
+                    next.play(cell);
+                    if next.moves == CELLS {
+                        Score::DRAW
+                    } else {
+                        // Score of the opponent, one ply further down.
+                        let value = self.solve_position(&next);
+                        (-value_to_score(value, next.moves)).to_root(1)
+                    }
+                };
+                Option::Some((col as u8 + 1, score))
+            })
+            .collect()
+    }
+
+    fn start(&mut self) {
+        self.started_at = self.nodes;
+        self.aborted = false;
+    }
+
+    /// Narrows down the value of the position with null-window searches, each
+    /// telling if the value is above or below a guess. Guesses lean towards
+    /// zero, as searches around the actual value take the longest.
+    fn solve_position(&mut self, position: &Position) -> i32 {
+        if position.can_win_next() {
+            return (CELLS + 1 - position.moves) / 2;
+        }
+
+        let mut min = -(CELLS - position.moves) / 2;
+        let mut max = (CELLS + 1 - position.moves) / 2;
+
+        while min < max && !self.aborted {
+            let mut guess = min + (max - min) / 2;
+            if guess <= 0 && min / 2 < guess {
+                guess = min / 2;
+            } else if guess >= 0 && max / 2 > guess {
+                guess = max / 2;
+            }
+
+            let value = self.negamax(position, guess, guess + 1);
+            if value <= guess {
+                max = value;
+            } else {
+                min = value;
+            }
+        }
+        min
+    }
+
+    /// Value of the position, if it's between alpha and beta. Otherwise it's
+    /// a bound on the value, on the same side of the window as the value.
+    /// Side to move can't win right away.
+    fn negamax(&mut self, position: &Position, mut alpha: i32, mut beta: i32) -> i32 {
+        self.nodes += 1;
+        if self.aborted
+            || self
+                .node_limit
+                .is_some_and(|l| self.nodes - self.started_at > l)
+        {
+            self.aborted = true;
+            return 0;
+        }
+
+        let moves = position.non_losing_moves();
+        if moves == 0 {
+            // Opponent wins on their next move, whatever's played.
+            return -(CELLS - position.moves) / 2;
+        }
+        if position.moves >= CELLS - 2 {
+            // Neither player can win with the last two tokens.
+            return 0;
+        }
+
+        // Opponent can't win on their next move, so the soonest they can is
+        // on the one after.
+        let min = -(CELLS - 2 - position.moves) / 2;
+        if alpha < min {
+            alpha = min;
+            if alpha >= beta {
+                return alpha;
+            }
+        }
+
+        // Side to move can't win right away, so the soonest it can is on its
+        // next move, unless the table knows better.
+        let max = self
+            .table
+            .get(position.key())
+            .unwrap_or((CELLS - 1 - position.moves) / 2);
+        if beta > max {
+            beta = max;
+            if alpha >= beta {
+                return beta;
+            }
+        }
+
+        let (ordered, len) = position.ordered_moves(moves);
+        for cell in ordered.iter().take(len) {
+            let mut next = *position;
+            next.play(*cell);
+
+            let value = -self.negamax(&next, -beta, -alpha);
+            if value >= beta {
+                return value;
+            }
+            alpha = alpha.max(value);
+        }
+
+        // Values of an aborted search can't be trusted, so keep them out of
+        // the table.
+        if self.aborted {
+            return 0;
+        }
+
+        // Every move was searched, and none was better than alpha.
+        self.table.store(position.key(), alpha);
+        alpha
+    }
+}
+
+/// Converts the value of the solver to a score, for the position with so many
+/// moves played. Winner's tokens are played every other ply, so the value
+/// tells on which ply the game is won.
+fn value_to_score(value: i32, moves: i32) -> Score {
+    // Tokens on the board before the winning move, which are even when the
+    // player moving first wins.
+    let before_win =
+        |winner_moves_first: bool| CELLS - 2 * value.abs() + if winner_moves_first { 0 } else { 1 };
+
+    match value.cmp(&0) {
+        Ordering::Equal => Score::DRAW,
+        Ordering::Greater => Score::win_in((before_win(moves % 2 == 0) - moves + 1) as u8),
+        Ordering::Less => Score::loss_in((before_win(moves % 2 == 1) - moves + 1) as u8),
+    }
+}
+
+// Module tests!!
+
+/// Plain minimax over every possible game, scoring wins by the ply they
+/// happen on, counted from the first call.
+#[cfg(test)]
+fn brute_force(game: &Connect4, ply: u8) -> Score {
+    (1..=COL_NUM as u8)
+        .filter_map(|col| {
+            let mut next = game.clone();
+            next.drop_token_in_col(col).ok()?;
+            Option::Some(match next.get_outcome() {
+                GameOutcome::Won(_, _) => Score::win_in(ply + 1),
+                GameOutcome::Draw => Score::DRAW,
+                GameOutcome::InProgress => {
+                    next.switch_players();
+                    -brute_force(&next, ply + 1)
+                }
+            })
+        })
+        .max()
+        .unwrap()
+}
+
+/// Random games, stopped with a few cells left, which haven't been won yet.
+#[cfg(test)]
+fn endgame_positions(cells_left: u8, count: usize) -> Vec<Connect4> {
+    use crate::enums::difficulty::Difficulty;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    let mut rng = StdRng::seed_from_u64(2024);
+    let mut positions = vec![];
+
+    while positions.len() < count {
+        let mut game = Connect4::init_vs_ai(Difficulty::Perfect);
+        while game.get_outcome() == GameOutcome::InProgress
+            && game.get_board().count_moves_left() > cells_left
+        {
+            if game.drop_token_in_col(rng.gen_range(1..=7)).is_ok() {
+                game.switch_players();
+            }
+        }
+        if game.get_outcome() == GameOutcome::InProgress {
+            positions.push(game);
+        }
+    }
+    positions
+}
+
+#[test]
+fn solves_endgames_the_same_as_brute_force() {
+    let mut solver = Solver::init();
+
+    endgame_positions(9, 40).iter().for_each(|game| {
+        assert_eq!(solver.solve(game), brute_force(game, 0));
+    });
+}
+
+#[test]
+#[should_panic]
+fn tables_too_small_to_tell_positions_apart_are_refused() {
+    Solver::init_with_size(1021);
+}
+
+#[test]
+fn column_scores_match_brute_force() {
+    // Smallest table allowed, so entries are overwritten the most.
+    let mut solver = Solver::init_with_size(131_101);
+
+    endgame_positions(8, 20).iter().for_each(|game| {
+        solver
+            .get_column_scores(game)
+            .iter()
+            .for_each(|(col, score)| {
+                let mut next = game.clone();
+                next.drop_token_in_col(*col).unwrap();
+                let expected = match next.get_outcome() {
+                    GameOutcome::Won(_, _) => Score::win_in(1),
+                    GameOutcome::Draw => Score::DRAW,
+                    GameOutcome::InProgress => {
+                        next.switch_players();
+                        -brute_force(&next, 1)
+                    }
+                };
+                assert_eq!(*score, expected);
+            });
+    });
+}
+
+#[test]
+fn solves_known_positions() {
+    use crate::enums::difficulty::Difficulty;
+
+    let mut solver = Solver::init();
+
+    // Positions from the test suites of Pascal Pons' Connect 4 solver, with
+    // the columns played from the start, and their values.
+    [
+        ("2252576253462244111563365343671351441", -1),
+        ("23163416124767223154467471272416755633", 0),
+        ("65214673556155731566316327373221417", -1),
+    ]
+    .iter()
+    .for_each(|(moves, value)| {
+        let game = game_after(Difficulty::Perfect, moves);
+        let position = Position::init_from(&game);
+        assert_eq!(solver.solve_position(&position), *value, "{}", moves);
+    });
+}
+
+#[test]
+fn wins_are_counted_in_plies() {
+    use crate::enums::difficulty::Difficulty;
+
+    let mut solver = Solver::init();
+
+    // Player one wins right away in column 1.
+    assert_eq!(
+        solver.solve(&game_after(Difficulty::Perfect, "121212")),
+        Score::win_in(1)
+    );
+
+    // Game that's already won.
+    assert_eq!(
+        solver.solve(&game_after(Difficulty::Perfect, "1212121")),
+        Score::loss_in(0)
+    );
+}
+
+#[test]
+fn gives_up_once_out_of_nodes() {
+    use crate::enums::difficulty::Difficulty;
+
+    let mut solver = Solver::init().with_node_limit(1000);
+
+    // Early in the game there's far too much to search.
+    solver.solve(&game_after(Difficulty::Perfect, "44"));
+    assert!(solver.is_aborted());
+
+    // Limit applies to each solve on its own.
+    let game = game_after(Difficulty::Perfect, "2252576253462244111563365343671351441");
+    assert_eq!(solver.solve(&game), Score::loss_in(4));
+    assert!(!solver.is_aborted());
+}
@@ -0,0 +1,41 @@
+//! Fixtures shared by the tests of the other modules.
+
+use crate::connect4::Connect4;
+use crate::enums::difficulty::Difficulty;
+use crate::models::board::{COL_NUM, ROW_NUM};
+
+/// Game after the moves, given as the columns played in turn, counted from
+/// one, like "4453".
+pub fn game_after(difficulty: Difficulty, moves: &str) -> Connect4 {
+    Connect4::init_from_moves(difficulty, moves).unwrap()
+}
+
+/// Board filled without anyone winning, but for the top `empty` cells of the
+/// last column, with whoever's turn it is to move. Columns are filled in
+/// blocks of two tokens, with the middle column flipped, so neither player
+/// ever gets four in a row.
+pub fn drawn_game(difficulty: Difficulty, empty: u8) -> Connect4 {
+    let mut game = Connect4::init_vs_ai(difficulty);
+    (1..=COL_NUM as u8).for_each(|col| {
+        let offset = if col == 4 { 1 } else { 0 };
+        let rows = if col == COL_NUM as u8 {
+            ROW_NUM as u8 - empty
+        } else {
+            ROW_NUM as u8
+        };
+        (0..rows).for_each(|row| {
+            let by_ai = (row / 2 + offset) % 2 == 1;
+            if by_ai {
+                game.switch_players();
+            }
+            game.drop_token_in_col(col).unwrap();
+            if by_ai {
+                game.switch_players();
+            }
+        });
+    });
+    if empty % 2 == 1 {
+        game.switch_players();
+    }
+    game
+}
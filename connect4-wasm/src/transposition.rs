@@ -0,0 +1,147 @@
+//! Transposition table, caching results of the minimax search for positions
+//! that can be reached through different move orders.
+
+use crate::score::Score;
+
+/// Default number of entries in the table.
+pub const DEFAULT_TABLE_SIZE: usize = 1 << 16;
+
+/// Tells how the stored score relates to the real score of the position. As
+/// alpha-beta cuts the search short, the score is often only a bound.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bound {
+    Exact,
+    Lower, // Real score is the same or higher
+    Upper, // Real score is the same or lower
+}
+
+/// What to do when a new entry lands on a slot that's already taken.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplacementPolicy {
+    Always,         // The newest entry always wins
+    DepthPreferred, // Keep the entry that was searched deeper
+}
+
+/// Single cached search result. The score is always from the point of view
+/// of the player whose turn it is in the position, with wins and losses
+/// counted in plies from the position rather than the root of the search.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Entry {
+    pub key: u64,
+    pub depth: u8,
+    pub score: Score,
+    pub bound: Bound,
+    pub best_col: u8,
+}
+
+#[derive(Debug, Clone)]
+pub struct TranspositionTable {
+    entries: Vec<Option<Entry>>,
+    policy: ReplacementPolicy,
+}
+
+impl TranspositionTable {
+    /// Creates an empty table with a fixed number of slots.
+    pub fn init(size: usize, policy: ReplacementPolicy) -> TranspositionTable {
+        TranspositionTable {
+            entries: vec![Option::None; size.max(1)],
+            policy,
+        }
+    }
+
+    /// Table used by the search when nothing else is configured.
+    pub fn init_default() -> TranspositionTable {
+        TranspositionTable::init(DEFAULT_TABLE_SIZE, ReplacementPolicy::DepthPreferred)
+    }
+
+    pub fn size(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Finds the entry stored for the position key, if there is one.
+    pub fn get(&self, key: u64) -> Option<Entry> {
+        self.entries[self.slot(key)].filter(|entry| entry.key == key)
+    }
+
+    /// Stores the entry, unless the replacement policy prefers what's already
+    /// stored in the slot.
+    pub fn store(&mut self, entry: Entry) {
+        let slot = self.slot(entry.key);
+
+        let replace = match (self.entries[slot], &self.policy) {
+            (Option::None, _) => true,
+            (Option::Some(_), ReplacementPolicy::Always) => true,
+            (Option::Some(old), ReplacementPolicy::DepthPreferred) => {
+                old.key == entry.key || entry.depth >= old.depth
+            }
+        };
+
+        if replace {
+            self.entries[slot] = Option::Some(entry);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries
+            .iter_mut()
+            .for_each(|entry| *entry = Option::None);
+    }
+
+    fn slot(&self, key: u64) -> usize {
+        (key % self.entries.len() as u64) as usize
+    }
+}
+
+// Module tests!!
+
+#[cfg(test)]
+fn test_entry(key: u64, depth: u8) -> Entry {
+    Entry {
+        key,
+        depth,
+        score: Score::from_heuristic(1),
+        bound: Bound::Exact,
+        best_col: 4,
+    }
+}
+
+#[test]
+fn can_store_and_get_entries() {
+    let mut table = TranspositionTable::init(16, ReplacementPolicy::Always);
+    table.store(test_entry(5, 2));
+
+    assert_eq!(table.get(5), Option::Some(test_entry(5, 2)));
+    // Same slot, but a different position.
+    assert_eq!(table.get(21), Option::None);
+    assert_eq!(table.get(6), Option::None);
+
+    table.clear();
+    assert_eq!(table.get(5), Option::None);
+}
+
+#[test]
+fn always_replaces_colliding_entries() {
+    let mut table = TranspositionTable::init(16, ReplacementPolicy::Always);
+    table.store(test_entry(5, 4));
+    table.store(test_entry(21, 1));
+
+    assert_eq!(table.get(5), Option::None);
+    assert_eq!(table.get(21), Option::Some(test_entry(21, 1)));
+}
+
+#[test]
+fn depth_preferred_keeps_deeper_entries() {
+    let mut table = TranspositionTable::init(16, ReplacementPolicy::DepthPreferred);
+    table.store(test_entry(5, 4));
+    table.store(test_entry(21, 1));
+
+    assert_eq!(table.get(5), Option::Some(test_entry(5, 4)));
+    assert_eq!(table.get(21), Option::None);
+
+    // Deeper results replace shallower ones, and the same position is always
+    // updated.
+    table.store(test_entry(21, 6));
+    assert_eq!(table.get(21), Option::Some(test_entry(21, 6)));
+    table.store(test_entry(21, 2));
+    assert_eq!(table.get(21), Option::Some(test_entry(21, 2)));
+}
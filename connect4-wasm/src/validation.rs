@@ -0,0 +1,296 @@
+//! Checks of the board sent from the outside, before a game is set up from it.
+//! Problems are collected rather than stopping at the first one, so the UI can
+//! show everything that's wrong with the input at once.
+
+use crate::enums::player::{player_to_json_str, Player};
+use crate::models::board::{has_four_in_a_row, Bitboard, Board, COL_HEIGHT, COL_NUM, ROW_NUM};
+use serde::{Deserialize, Serialize};
+
+/// Single problem found in the input board. Columns and rows are counted from
+/// zero, the same way the board is indexed in the input.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum Diagnostic {
+    /// Board doesn't have exactly one entry per column.
+    WrongColumnCount { expected: u8, found: usize },
+    /// Column holds more cells than the board has rows.
+    ColumnTooTall { col: u8, height: usize },
+    /// Cell is neither a token of one of the players, nor empty.
+    UnknownCell { col: u8, row: u8, value: String },
+    /// Token with an empty cell somewhere below it.
+    FloatingToken { col: u8, row: u8 },
+    /// Player moves first, so they have the same number of tokens as the AI,
+    /// or just one more.
+    TokenCountMismatch { player: u8, ai: u8 },
+    /// Token counts are fine, but say it's the other player's turn.
+    WrongSideToMove { expected: String, found: String },
+    /// Player already had four in a row before the last move was played,
+    /// either because it's their turn, or because none of the tokens they
+    /// could have played last completes it.
+    GameAlreadyOver { winner: String },
+    /// Both players have four in a row.
+    TwoWinners,
+}
+
+/// Validates the board from the input, with `to_move` being the player whose
+/// turn it is. Returns an empty list when the board is fine.
+pub fn validate_board(str_board: &[Vec<String>], to_move: &Player) -> Vec<Diagnostic> {
+    let diagnostics = validate_shape(str_board);
+    if !diagnostics.is_empty() {
+        return diagnostics;
+    }
+
+    let board = Board::init_from(str_board);
+    let players_tokens = board.get_tokens_for_player(&Player::One);
+    let ai_tokens = board.get_tokens_for_player(&Player::AI);
+    let player_count = players_tokens.count_ones() as u8;
+    let ai_count = ai_tokens.count_ones() as u8;
+
+    if player_count != ai_count && player_count != ai_count + 1 {
+        return vec![Diagnostic::TokenCountMismatch {
+            player: player_count,
+            ai: ai_count,
+        }];
+    }
+
+    let found = if player_count == ai_count {
+        Player::One
+    } else {
+        Player::AI
+    };
+    if found != *to_move {
+        return vec![Diagnostic::WrongSideToMove {
+            expected: String::from(player_to_json_str(to_move)),
+            found: String::from(player_to_json_str(&found)),
+        }];
+    }
+
+    validate_winners(players_tokens, ai_tokens, to_move)
+}
+
+/// Checks the number of columns and rows, the values of the cells, and that
+/// all tokens are resting on something.
+fn validate_shape(str_board: &[Vec<String>]) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+
+    if str_board.len() != COL_NUM as usize {
+        diagnostics.push(Diagnostic::WrongColumnCount {
+            expected: COL_NUM as u8,
+            found: str_board.len(),
+        });
+    }
+
+    str_board.iter().enumerate().for_each(|(i, col)| {
+        if col.len() > ROW_NUM as usize {
+            diagnostics.push(Diagnostic::ColumnTooTall {
+                col: i as u8,
+                height: col.len(),
+            });
+        }
+
+        let mut found_empty = false;
+        col.iter()
+            .enumerate()
+            .for_each(|(j, val)| match val.as_str() {
+                "" => found_empty = true,
+                "player" | "ai" if found_empty => diagnostics.push(Diagnostic::FloatingToken {
+                    col: i as u8,
+                    row: j as u8,
+                }),
+                "player" | "ai" => {}
+                _ => diagnostics.push(Diagnostic::UnknownCell {
+                    col: i as u8,
+                    row: j as u8,
+                    value: val.clone(),
+                }),
+            });
+    });
+
+    diagnostics
+}
+
+fn validate_winners(
+    players_tokens: Bitboard,
+    ai_tokens: Bitboard,
+    to_move: &Player,
+) -> Vec<Diagnostic> {
+    match (
+        has_four_in_a_row(players_tokens),
+        has_four_in_a_row(ai_tokens),
+    ) {
+        (true, true) => vec![Diagnostic::TwoWinners],
+        (true, false) => validate_winner(&Player::One, players_tokens, ai_tokens, to_move),
+        (false, true) => validate_winner(&Player::AI, ai_tokens, players_tokens, to_move),
+        (false, false) => vec![],
+    }
+}
+
+/// Winner has to be the one who played last, and the game has to have ended
+/// with that move.
+fn validate_winner(
+    winner: &Player,
+    tokens: Bitboard,
+    opponent_tokens: Bitboard,
+    to_move: &Player,
+) -> Vec<Diagnostic> {
+    if winner == to_move || !could_have_just_won(tokens, opponent_tokens) {
+        vec![Diagnostic::GameAlreadyOver {
+            winner: String::from(player_to_json_str(winner)),
+        }]
+    } else {
+        vec![]
+    }
+}
+
+/// Tells if one of the tokens on top of a column, which could have been the
+/// last one played, completes every four in a row of the player.
+fn could_have_just_won(tokens: Bitboard, opponent_tokens: Bitboard) -> bool {
+    let played = tokens | opponent_tokens;
+
+    (0..COL_NUM as usize).any(|col| {
+        let column = played & (((1 << COL_HEIGHT) - 1) << (col * COL_HEIGHT as usize));
+        if column == 0 {
+            return false;
+        }
+
+        let top = 1 << (Bitboard::BITS - 1 - column.leading_zeros());
+        tokens & top != 0 && !has_four_in_a_row(tokens ^ top)
+    })
+}
+
+// Module tests!!
+
+#[cfg(test)]
+fn str_board(cols: &[&[&str]]) -> Vec<Vec<String>> {
+    cols.iter()
+        .map(|col| col.iter().map(|val| String::from(*val)).collect())
+        .collect()
+}
+
+#[test]
+fn accepts_valid_boards() {
+    let empty = str_board(&[&[], &[], &[], &[], &[], &[], &[]]);
+    assert_eq!(validate_board(&empty, &Player::One), vec![]);
+
+    let board = str_board(&[&["player", ""], &["ai"], &["player"], &[], &[], &[], &[]]);
+    assert_eq!(validate_board(&board, &Player::AI), vec![]);
+}
+
+#[test]
+fn reports_every_problem_with_the_shape() {
+    let board = str_board(&[
+        &["player", "ai", "player", "ai", "player", "ai", "player"],
+        &["", "ai"],
+        &["red"],
+        &[],
+        &[],
+        &[],
+        &[],
+        &[],
+    ]);
+
+    assert_eq!(
+        validate_board(&board, &Player::AI),
+        vec![
+            Diagnostic::WrongColumnCount {
+                expected: 7,
+                found: 8
+            },
+            Diagnostic::ColumnTooTall { col: 0, height: 7 },
+            Diagnostic::FloatingToken { col: 1, row: 1 },
+            Diagnostic::UnknownCell {
+                col: 2,
+                row: 0,
+                value: String::from("red")
+            },
+        ]
+    );
+}
+
+#[test]
+fn checks_token_counts_and_side_to_move() {
+    let board = str_board(&[&["ai", "ai"], &[], &[], &[], &[], &[], &[]]);
+    assert_eq!(
+        validate_board(&board, &Player::One),
+        vec![Diagnostic::TokenCountMismatch { player: 0, ai: 2 }]
+    );
+
+    let board = str_board(&[&["player"], &[], &[], &[], &[], &[], &[]]);
+    assert_eq!(
+        validate_board(&board, &Player::One),
+        vec![Diagnostic::WrongSideToMove {
+            expected: String::from("player"),
+            found: String::from("ai")
+        }]
+    );
+}
+
+#[test]
+fn checks_who_has_already_won() {
+    // Player has just won, which is fine with the AI to move.
+    let board = str_board(&[&["player"; 4], &["ai"; 3], &[], &[], &[], &[], &[]]);
+    assert_eq!(validate_board(&board, &Player::AI), vec![]);
+
+    // AI won before the player made their last move.
+    let board = str_board(&[
+        &["ai"; 4],
+        &["player"; 3],
+        &["player"; 2],
+        &[],
+        &[],
+        &[],
+        &[],
+    ]);
+    assert_eq!(
+        validate_board(&board, &Player::One),
+        vec![Diagnostic::WrongSideToMove {
+            expected: String::from("player"),
+            found: String::from("ai")
+        }]
+    );
+    assert_eq!(
+        validate_board(&board, &Player::AI),
+        vec![Diagnostic::GameAlreadyOver {
+            winner: String::from("ai")
+        }]
+    );
+
+    let board = str_board(&[&["player"; 4], &["ai"; 4], &[], &[], &[], &[], &[]]);
+    assert_eq!(
+        validate_board(&board, &Player::One),
+        vec![Diagnostic::TwoWinners]
+    );
+}
+
+#[test]
+fn wins_followed_by_more_tokens_are_over() {
+    // Player won in the first column, and the AI covered it, after which the
+    // player played again.
+    let board = str_board(&[
+        &["player", "player", "player", "player", "ai"],
+        &["ai"; 3],
+        &[],
+        &[],
+        &["player"],
+        &[],
+        &[],
+    ]);
+    assert_eq!(
+        validate_board(&board, &Player::AI),
+        vec![Diagnostic::GameAlreadyOver {
+            winner: String::from("player")
+        }]
+    );
+
+    // Winning token could have been the last one played on its own.
+    let board = str_board(&[
+        &["player"; 4],
+        &["ai"; 3],
+        &["ai"],
+        &[],
+        &["player"],
+        &[],
+        &[],
+    ]);
+    assert_eq!(validate_board(&board, &Player::AI), vec![]);
+}
@@ -0,0 +1,497 @@
+//! Message protocol for running the AI search in a Web Worker, so long searches
+//! don't freeze the page. Messages are JSON, tagged by their `type`.
+//!
+//! The page posts a `start` request, and the worker hands it to `handle`. The
+//! search then runs one depth per call to `step`, with each call returning a
+//! `progress` response, until the last one returns the `result`. Worker should
+//! schedule the steps (with `setTimeout` for example) instead of looping, so
+//! a `cancel` request can be handled in between. Engines other than minimax
+//! choose their column all at once, in a single step.
+
+use crate::clock::{Clock, Deadline, SystemClock};
+use crate::control::Progress;
+use crate::engine::EngineRegistry;
+use crate::enums::difficulty::{difficulty_to_depth, difficulty_to_time_budget, Difficulty};
+use crate::enums::game_outcome::is_game_over;
+use crate::enums::player::Player;
+use crate::error::Connect4Error;
+use crate::minimax::Deepening;
+use crate::personality::{Personality, PersonalityInput};
+use crate::session::GameSession;
+use crate::transposition::TranspositionTable;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+/// Message from the page to the worker. Board is sent the same way as to
+/// `ai_move`, with the AI to move, and so are the optional seed, engine and
+/// personality.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WorkerRequest {
+    Start {
+        id: u32,
+        difficulty: Difficulty,
+        board: Vec<Vec<String>>,
+        #[serde(default)]
+        seed: Option<u64>,
+        #[serde(default)]
+        engine: Option<serde_json::Value>,
+        #[serde(default)]
+        personality: Option<PersonalityInput>,
+    },
+    Cancel {
+        id: u32,
+    },
+}
+
+/// Message from the worker back to the page. Columns are counted from one, and
+/// nodes are counted over all the depths searched.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WorkerResponse {
+    /// Search finished another depth, and this is the best column so far.
+    Progress {
+        id: u32,
+        depth: u8,
+        nodes: u64,
+        col: Option<u8>,
+    },
+    /// Search is over, and this is the column the AI plays.
+    Result {
+        id: u32,
+        depth: u8,
+        nodes: u64,
+        col: Option<u8>,
+    },
+    Cancelled {
+        id: u32,
+    },
+    /// Request couldn't be handled. There's no id if the message couldn't be
+    /// read in the first place.
+    Error {
+        id: Option<u32>,
+        error: Connect4Error,
+    },
+}
+
+/// Search the worker is currently running. Minimax deepens one depth per
+/// step, any other engine plays from the session in a single step.
+#[derive(Debug)]
+struct ActiveSearch {
+    id: u32,
+    session: GameSession,
+    deepening: Option<Deepening>,
+    started_ms: f64,
+    budget_ms: Option<f64>,
+}
+
+/// Runs one search at a time. Starting a new search drops the old one.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct SearchWorker {
+    active: Option<ActiveSearch>,
+    table: TranspositionTable,
+}
+
+impl Default for SearchWorker {
+    fn default() -> SearchWorker {
+        SearchWorker::init()
+    }
+}
+
+impl SearchWorker {
+    pub fn init() -> SearchWorker {
+        SearchWorker {
+            active: Option::None,
+            table: TranspositionTable::init_default(),
+        }
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.active.is_some()
+    }
+
+    /// Handles a message from the page. Starting a search has no response,
+    /// the steps of the search report back instead.
+    pub fn handle_message(&mut self, message: &str, clock: &dyn Clock) -> Option<WorkerResponse> {
+        match serde_json::from_str::<WorkerRequest>(message) {
+            Ok(request) => self.handle_request(request, clock),
+            Err(err) => Option::Some(WorkerResponse::Error {
+                id: Option::None,
+                error: Connect4Error::from(err),
+            }),
+        }
+    }
+
+    pub fn handle_request(
+        &mut self,
+        request: WorkerRequest,
+        clock: &dyn Clock,
+    ) -> Option<WorkerResponse> {
+        match request {
+            WorkerRequest::Start {
+                id,
+                difficulty,
+                board,
+                seed,
+                engine,
+                personality,
+            } => match init_session(difficulty, board, seed, engine, personality)
+                .and_then(|session| start_search(id, session, clock))
+            {
+                Ok(search) => {
+                    self.active = Option::Some(search);
+                    Option::None
+                }
+                Err(error) => Option::Some(WorkerResponse::Error {
+                    id: Option::Some(id),
+                    error,
+                }),
+            },
+            WorkerRequest::Cancel { id } => match &self.active {
+                Option::Some(search) if search.id == id => {
+                    self.active = Option::None;
+                    Option::Some(WorkerResponse::Cancelled { id })
+                }
+                _ => Option::None,
+            },
+        }
+    }
+
+    /// Searches the next depth. Returns nothing if there's no search running.
+    pub fn run_step(&mut self, clock: &dyn Clock) -> Option<WorkerResponse> {
+        let search = self.active.as_mut()?;
+        let id = search.id;
+
+        let deepening = match search.deepening.as_mut() {
+            Option::Some(deepening) => deepening,
+            Option::None => {
+                let mut last = Option::None;
+                let col = search
+                    .session
+                    .play_ai_controlled(Option::None, &mut |progress: &Progress| {
+                        last = Option::Some(*progress)
+                    })
+                    .ok()
+                    .map(|(col, _)| col);
+                let (depth, nodes) = last.map_or((0, 0), |p| (p.depth, p.nodes));
+                self.active = Option::None;
+                return Option::Some(WorkerResponse::Result {
+                    id,
+                    depth,
+                    nodes,
+                    col,
+                });
+            }
+        };
+
+        // First depth always runs in full, so there's a column to play.
+        let deadline = search
+            .budget_ms
+            .filter(|_| deepening.get_depth().is_some())
+            .map(|budget_ms| {
+                Deadline::init(clock, budget_ms - (clock.now_ms() - search.started_ms))
+            });
+        let completed = deepening.step(
+            search.session.get_game(),
+            &mut self.table,
+            deadline.as_ref(),
+            Option::None,
+        );
+
+        let (depth, nodes, col) = deepening
+            .get_progress()
+            .map_or((0, 0, Option::None), |p| (p.depth, p.nodes, p.best_col));
+
+        if completed && !deepening.is_done() {
+            Option::Some(WorkerResponse::Progress {
+                id,
+                depth,
+                nodes,
+                col,
+            })
+        } else {
+            self.active = Option::None;
+            Option::Some(WorkerResponse::Result {
+                id,
+                depth,
+                nodes,
+                col,
+            })
+        }
+    }
+}
+
+/// Session the AI searches from, set up the same way as for `ai_move`.
+fn init_session(
+    difficulty: Difficulty,
+    board: Vec<Vec<String>>,
+    seed: Option<u64>,
+    engine: Option<serde_json::Value>,
+    personality: Option<PersonalityInput>,
+) -> Result<GameSession, Connect4Error> {
+    let mut session = GameSession::init_from_board(difficulty, board, Player::AI)?;
+    session.set_seed(seed);
+    if let Option::Some(engine) = engine {
+        session.select_engine(&EngineRegistry::init(), engine)?;
+    }
+    if let Option::Some(personality) = personality {
+        session.set_personality(Option::Some(Personality::try_from(personality)?))?;
+    }
+    Ok(session)
+}
+
+fn start_search(
+    id: u32,
+    session: GameSession,
+    clock: &dyn Clock,
+) -> Result<ActiveSearch, Connect4Error> {
+    let game = session.get_game();
+    if is_game_over(&game.get_outcome()) {
+        return Err(Connect4Error::GameOver);
+    }
+
+    let deepening = if session.get_engine().get_name() == "minimax" {
+        Option::Some(Deepening::init(game, difficulty_to_depth(game.difficulty)))
+    } else {
+        Option::None
+    };
+    Ok(ActiveSearch {
+        id,
+        budget_ms: difficulty_to_time_budget(game.difficulty),
+        deepening,
+        session,
+        started_ms: clock.now_ms(),
+    })
+}
+
+fn response_to_json(response: WorkerResponse) -> String {
+    serde_json::to_string(&response).unwrap_or_default()
+}
+
+/// Worker API exposed to JS, taking and returning the messages as JSON.
+#[wasm_bindgen]
+impl SearchWorker {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> SearchWorker {
+        SearchWorker::init()
+    }
+
+    /// Handles a message from the page, returning the response to post back,
+    /// if there is one.
+    pub fn handle(&mut self, message: &str) -> Option<String> {
+        self.handle_message(message, &SystemClock)
+            .map(response_to_json)
+    }
+
+    /// Runs the next step of the search, returning the response to post back.
+    /// Returns nothing once there's no search to run.
+    pub fn step(&mut self) -> Option<String> {
+        self.run_step(&SystemClock).map(response_to_json)
+    }
+}
+
+// Module tests!!
+
+#[cfg(test)]
+const START_MESSAGE: &str = r#"{"type":"start","id":7,"difficulty":"normal","board":[["player","player","player"],["ai","ai"],[],[],[],[],[]]}"#;
+
+#[test]
+fn messages_are_tagged_by_type() {
+    assert_eq!(
+        serde_json::from_str::<WorkerRequest>(r#"{"type":"cancel","id":3}"#).unwrap(),
+        WorkerRequest::Cancel { id: 3 }
+    );
+    assert!(matches!(
+        serde_json::from_str::<WorkerRequest>(START_MESSAGE).unwrap(),
+        WorkerRequest::Start {
+            id: 7,
+            difficulty: Difficulty::Normal,
+            ..
+        }
+    ));
+
+    let response = WorkerResponse::Progress {
+        id: 7,
+        depth: 2,
+        nodes: 120,
+        col: Option::Some(4),
+    };
+    let json = serde_json::to_string(&response).unwrap();
+    assert_eq!(
+        json,
+        r#"{"type":"progress","id":7,"depth":2,"nodes":120,"col":4}"#
+    );
+    assert_eq!(
+        serde_json::from_str::<WorkerResponse>(&json).unwrap(),
+        response
+    );
+
+    let response = WorkerResponse::Error {
+        id: Option::Some(1),
+        error: Connect4Error::GameOver,
+    };
+    let json = serde_json::to_string(&response).unwrap();
+    assert_eq!(
+        json,
+        r#"{"type":"error","id":1,"error":{"code":"game_over"}}"#
+    );
+    assert_eq!(
+        serde_json::from_str::<WorkerResponse>(&json).unwrap(),
+        response
+    );
+}
+
+#[test]
+fn search_runs_one_depth_per_step() {
+    use crate::clock::FakeClock;
+
+    let clock = FakeClock::init(0.0);
+    let mut worker = SearchWorker::init();
+    assert_eq!(worker.run_step(&clock), Option::None);
+    assert_eq!(worker.handle_message(START_MESSAGE, &clock), Option::None);
+
+    // Normal difficulty searches depths 0 to 3. Once it looks ahead, the AI
+    // sees it has to block column 1.
+    (0..3).for_each(|depth| {
+        assert!(matches!(
+            worker.run_step(&clock),
+            Option::Some(WorkerResponse::Progress { id: 7, depth: d, .. }) if d == depth
+        ));
+    });
+    assert!(matches!(
+        worker.run_step(&clock),
+        Option::Some(WorkerResponse::Result {
+            id: 7,
+            depth: 3,
+            nodes,
+            col: Option::Some(1)
+        }) if nodes > 0
+    ));
+    assert!(!worker.is_searching());
+    assert_eq!(worker.run_step(&clock), Option::None);
+}
+
+#[test]
+fn search_can_be_cancelled() {
+    use crate::clock::FakeClock;
+
+    let clock = FakeClock::init(0.0);
+    let mut worker = SearchWorker::init();
+    worker.handle_message(START_MESSAGE, &clock);
+    worker.run_step(&clock);
+
+    // Only the running search can be cancelled.
+    assert_eq!(
+        worker.handle_message(r#"{"type":"cancel","id":8}"#, &clock),
+        Option::None
+    );
+    assert_eq!(
+        worker.handle_message(r#"{"type":"cancel","id":7}"#, &clock),
+        Option::Some(WorkerResponse::Cancelled { id: 7 })
+    );
+    assert_eq!(worker.run_step(&clock), Option::None);
+}
+
+#[test]
+fn time_budget_ends_the_search_early() {
+    use crate::clock::FakeClock;
+
+    let clock = FakeClock::init(20.0);
+    let mut worker = SearchWorker::init();
+    let start = START_MESSAGE.replace(r#""normal""#, r#"{"time_ms":25}"#);
+    worker.handle_message(&start, &clock);
+
+    // First depth always finishes, the next one runs out of time.
+    assert!(matches!(
+        worker.run_step(&clock),
+        Option::Some(WorkerResponse::Progress { depth: 0, .. })
+    ));
+    assert!(matches!(
+        worker.run_step(&clock),
+        Option::Some(WorkerResponse::Result {
+            id: 7,
+            depth: 0,
+            ..
+        })
+    ));
+    assert!(!worker.is_searching());
+}
+
+#[test]
+fn bad_requests_get_an_error() {
+    use crate::clock::FakeClock;
+
+    let clock = FakeClock::init(0.0);
+    let mut worker = SearchWorker::init();
+
+    assert!(matches!(
+        worker.handle_message(r#"{"type":"pause"}"#, &clock),
+        Option::Some(WorkerResponse::Error {
+            id: Option::None,
+            error: Connect4Error::InvalidJson { .. }
+        })
+    ));
+
+    let start = START_MESSAGE.replace(r#"["ai","ai"]"#, r#"["ai"]"#);
+    assert!(matches!(
+        worker.handle_message(&start, &clock),
+        Option::Some(WorkerResponse::Error {
+            id: Option::Some(7),
+            error: Connect4Error::BadShape { .. }
+        })
+    ));
+    assert!(!worker.is_searching());
+}
+
+#[test]
+fn other_engines_search_in_a_single_step() {
+    use crate::clock::FakeClock;
+
+    let clock = FakeClock::init(0.0);
+    let mut worker = SearchWorker::init();
+    let start = START_MESSAGE.replace(r#""board""#, r#""engine":"greedy","board""#);
+    worker.handle_message(&start, &clock);
+
+    // There's no deeper search to report progress on, just the result.
+    assert!(matches!(
+        worker.run_step(&clock),
+        Option::Some(WorkerResponse::Result {
+            id: 7,
+            col: Option::Some(_),
+            ..
+        })
+    ));
+    assert!(!worker.is_searching());
+}
+
+#[test]
+fn engine_and_personality_are_checked_when_starting() {
+    use crate::clock::FakeClock;
+
+    let clock = FakeClock::init(0.0);
+    let mut worker = SearchWorker::init();
+
+    let start = START_MESSAGE.replace(r#""board""#, r#""engine":"alphazero","board""#);
+    assert!(matches!(
+        worker.handle_message(&start, &clock),
+        Option::Some(WorkerResponse::Error {
+            id: Option::Some(7),
+            error: Connect4Error::UnknownEngine { .. }
+        })
+    ));
+
+    let start = START_MESSAGE.replace(r#""board""#, r#""personality":{"blunder_rate":2},"board""#);
+    assert!(matches!(
+        worker.handle_message(&start, &clock),
+        Option::Some(WorkerResponse::Error {
+            id: Option::Some(7),
+            error: Connect4Error::UnknownPersonality { .. }
+        })
+    ));
+    assert!(!worker.is_searching());
+
+    let start = START_MESSAGE.replace(r#""board""#, r#""personality":"casual","board""#);
+    assert_eq!(worker.handle_message(&start, &clock), Option::None);
+    assert!(worker.is_searching());
+}